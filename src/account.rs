@@ -1,519 +1,2795 @@
-use crate::{error::Error, transaction::TransactionState, ClientId, Transaction, TxId};
-use rust_decimal::Decimal;
-use serde::Serialize;
-use std::collections::{hash_map::Entry, HashMap};
+use crate::{
+    error::Error,
+    storage::Storage,
+    transaction::{Moment, TransactionState},
+    BalanceChange, ClientId, Currency, Event, HistoryEntry, LedgerConfig, Rounding, Timestamp,
+    Transaction, TransactionKind, TxId, TxRecord,
+};
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Lifecycle status of an [`Account`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountStatus {
+    /// All transactions are allowed
+    Active,
+    /// Deposits are allowed, withdrawals, outgoing transfers and authorizations are blocked
+    Frozen,
+    /// No transactions are allowed until the account is unlocked
+    Locked,
+    /// No transactions are allowed, the account cannot be reopened
+    Closed,
+}
 
-#[derive(Debug, Serialize)]
-pub struct Account {
-    #[serde(rename(serialize = "client"))]
-    pub id: ClientId,
+/// Balances of an [`Account`] in a single currency
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Balance {
     available: Decimal,
     held: Decimal,
+    /// Funds held by open authorizations, separate from the funds held by disputes
+    #[serde(default)]
+    authorized: Decimal,
     total: Decimal,
-    locked: bool,
-    #[serde(skip)]
-    transactions: HashMap<TxId, Transaction>,
+    #[serde(default)]
+    credit_limit: Decimal,
+}
+
+impl Balance {
+    pub fn available(&self) -> Decimal {
+        self.available
+    }
+
+    /// Gets the funds held by disputes
+    pub fn held(&self) -> Decimal {
+        self.held
+    }
+
+    /// Gets the funds held by open authorizations
+    pub fn authorized(&self) -> Decimal {
+        self.authorized
+    }
+
+    pub fn total(&self) -> Decimal {
+        self.total
+    }
+
+    /// Gets how far the available funds can go negative by withdrawals
+    pub fn credit_limit(&self) -> Decimal {
+        self.credit_limit
+    }
+
+    /// Gets the credit in use, the negative part of the available funds up to the credit limit
+    ///
+    /// Available funds below the credit limit, caused by a dispute, are not credit.
+    pub fn credit_used(&self) -> Decimal {
+        if self.available < Decimal::ZERO {
+            (-self.available).min(self.credit_limit)
+        } else {
+            Decimal::ZERO
+        }
+    }
+
+    fn compute_total(&mut self) {
+        self.total = self.available + self.held + self.authorized;
+    }
+
+    /// Checks if the available funds and the credit limit cover an amount, a credit limit that
+    /// overflows with the available funds covers any amount
+    fn covers(&self, amount: Decimal) -> bool {
+        self.available
+            .checked_add(self.credit_limit)
+            .is_none_or(|funds| amount <= funds)
+    }
+}
+
+/// Output row of an [`Account`], one for each currency held by the account
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct AccountRow {
+    pub client: ClientId,
+    pub currency: Option<Currency>,
+    pub available: Decimal,
+    pub held: Decimal,
+    pub authorized: Decimal,
+    pub total: Decimal,
+    pub locked: bool,
+    pub status: AccountStatus,
+    pub last_activity: Option<Timestamp>,
+    pub credit_limit: Decimal,
+    pub credit_used: Decimal,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Account {
+    pub id: ClientId,
+    #[serde(with = "balances")]
+    balances: BTreeMap<Option<Currency>, Balance>,
+    status: AccountStatus,
+    /// Sequence number of the next entry in the history
+    #[serde(default)]
+    sequence: u64,
+    #[serde(default)]
+    last_activity: Option<Timestamp>,
+    /// Transactions with an open dispute
+    #[serde(default)]
+    disputes: BTreeSet<TxId>,
+    /// Authorizations that still hold funds
+    #[serde(default)]
+    authorizations: BTreeSet<TxId>,
+    /// Number of successfully processed transactions of each kind
+    #[serde(default)]
+    counts: BTreeMap<TransactionKind, u64>,
+    /// Events that were not yet sent to the observers of the ledger
     #[serde(skip)]
-    tx_state: HashMap<TxId, TransactionState>,
+    events: Vec<Event<'static>>,
 }
 
 impl Account {
     pub(crate) fn new(id: ClientId) -> Self {
         Self {
             id,
-            available: Decimal::ZERO,
-            held: Decimal::ZERO,
-            total: Decimal::ZERO,
-            locked: false,
-            transactions: HashMap::new(),
-            tx_state: HashMap::new(),
+            balances: BTreeMap::new(),
+            status: AccountStatus::Active,
+            sequence: 0,
+            last_activity: None,
+            disputes: BTreeSet::new(),
+            authorizations: BTreeSet::new(),
+            counts: BTreeMap::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Gets the output rows of the account, sorted on currency
+    ///
+    /// An account without any balance yields a single row of zeroes without a currency.
+    pub fn rows(&self) -> impl Iterator<Item = AccountRow> + '_ {
+        let empty = self.balances.is_empty().then(|| (None, Balance::default()));
+        self.balances
+            .iter()
+            .map(|(currency, balance)| (*currency, *balance))
+            .chain(empty)
+            .map(|(currency, balance)| AccountRow {
+                client: self.id,
+                currency,
+                available: balance.available,
+                held: balance.held,
+                authorized: balance.authorized,
+                total: balance.total,
+                locked: self.locked(),
+                status: self.status,
+                last_activity: self.last_activity,
+                credit_limit: balance.credit_limit,
+                credit_used: balance.credit_used(),
+            })
+    }
+
+    /// Gets the balance in the given currency, zero if the account never held it
+    ///
+    /// # Example
+    /// ```rust
+    /// use ledger_rs::{Ledger, Transaction};
+    /// use rust_decimal::Decimal;
+    ///
+    /// let mut ledger = Ledger::new();
+    /// let tx = Transaction::Deposit {
+    ///     id: 1.into(),
+    ///     client: 7.into(),
+    ///     amount: Decimal::new(2, 0),
+    ///     currency: None,
+    ///     timestamp: None,
+    /// };
+    /// assert!(ledger.process_tx(tx).is_ok());
+    ///
+    /// let account = ledger.account(7.into()).unwrap().unwrap();
+    ///
+    /// assert_eq!(account.balance(None).available(), Decimal::new(2, 0));
+    /// assert_eq!(account.balance(None).held(), Decimal::ZERO);
+    /// assert!(!account.locked());
+    /// ```
+    pub fn balance(&self, currency: Option<Currency>) -> Balance {
+        self.balances.get(&currency).copied().unwrap_or_default()
+    }
+
+    /// Iterates over the balances of the account, sorted on currency
+    pub fn balances(&self) -> impl Iterator<Item = (Option<Currency>, Balance)> + '_ {
+        self.balances
+            .iter()
+            .map(|(currency, balance)| (*currency, *balance))
+    }
+
+    pub fn status(&self) -> AccountStatus {
+        self.status
+    }
+
+    pub fn locked(&self) -> bool {
+        self.status == AccountStatus::Locked
+    }
+
+    /// Gets the latest timestamp of the successfully processed transactions, if any had one
+    pub fn last_activity(&self) -> Option<Timestamp> {
+        self.last_activity
+    }
+
+    /// Iterates over the IDs of the transactions with an open dispute, in ascending order
+    pub fn open_disputes(&self) -> impl Iterator<Item = TxId> + '_ {
+        self.disputes.iter().copied()
+    }
+
+    /// Iterates over the IDs of the authorizations that still hold funds, in ascending order
+    pub fn open_authorizations(&self) -> impl Iterator<Item = TxId> + '_ {
+        self.authorizations.iter().copied()
+    }
+
+    /// Gets the number of successfully processed transactions of a kind
+    pub fn count(&self, kind: TransactionKind) -> u64 {
+        self.counts.get(&kind).copied().unwrap_or_default()
+    }
+
+    /// Gets the moment at which the next transaction would be processed
+    pub(crate) fn moment(&self, timestamp: Option<Timestamp>) -> Moment {
+        Moment {
+            sequence: self.sequence,
+            timestamp,
+        }
+    }
+
+    /// Takes the events of the processed transactions, in the order they happened
+    pub(crate) fn take_events(&mut self) -> Vec<Event<'static>> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Adds events that happened on another account, e.g. the receiving side of a transfer
+    pub(crate) fn add_events(&mut self, events: Vec<Event<'static>>) {
+        self.events.extend(events);
+    }
+
+    /// Records the change of a balance, `before` is the balance before the change
+    fn change(&self, currency: Option<Currency>, before: Balance) -> BalanceChange {
+        BalanceChange {
+            client: self.id,
+            currency,
+            before,
+            after: self.balance(currency),
         }
     }
 
-    pub(crate) fn process_tx(&mut self, tx: Transaction) -> Result<(), Error> {
+    pub(crate) fn process_tx<S: Storage + ?Sized>(
+        &mut self,
+        tx: Transaction,
+        storage: &mut S,
+        config: &LedgerConfig,
+    ) -> Result<(), Error> {
         if tx.client() != self.id {
             return Err(Error::Unauthorized(tx.client(), self.id));
         }
-        if self.locked {
-            return Err(Error::Locked(self.id));
+        match self.status {
+            AccountStatus::Closed => return Err(Error::Closed(self.id)),
+            AccountStatus::Locked
+                if !matches!(
+                    tx,
+                    Transaction::Unlock { .. }
+                        | Transaction::Close { .. }
+                        | Transaction::CreditLimit { .. }
+                ) =>
+            {
+                return Err(Error::Locked(self.id))
+            }
+            AccountStatus::Frozen
+                if matches!(
+                    tx,
+                    Transaction::Withdrawal { .. }
+                        | Transaction::Transfer { .. }
+                        | Transaction::Authorize { .. }
+                ) =>
+            {
+                return Err(Error::Frozen(self.id))
+            }
+            _ => {}
         }
-        match tx {
-            Transaction::Deposit { id, amount, .. } => match self.deposit(id, amount) {
-                Ok(()) => {
-                    self.transactions.insert(id, tx);
-                    Ok(())
+        let moment = self.moment(tx.timestamp());
+        let state = match tx {
+            Transaction::Deposit {
+                id,
+                amount,
+                currency,
+                ..
+            } => {
+                let amount = self.deposit(id, amount, currency, config)?;
+                storage.put_transaction(id, TxRecord::new(tx.clone(), amount, moment))?;
+                Some(TransactionState::Normal)
+            }
+            Transaction::Withdrawal {
+                id,
+                amount,
+                currency,
+                ..
+            } => {
+                let amount = self.withdrawal(id, amount, currency, config)?;
+                storage.put_transaction(id, TxRecord::new(tx.clone(), amount, moment))?;
+                Some(TransactionState::Normal)
+            }
+            Transaction::Transfer {
+                id,
+                to,
+                amount,
+                currency,
+                ..
+            } => {
+                if to == self.id {
+                    return Err(Error::SelfTransfer(self.id));
                 }
-                Err(e) => Err(e),
-            },
-            Transaction::Withdrawal { id, amount, .. } => match self.withdrawal(amount) {
-                Ok(()) => {
-                    self.transactions.insert(id, tx);
-                    Ok(())
+                let amount = self.withdrawal(id, amount, currency, config)?;
+                storage.put_transaction(id, TxRecord::new(tx.clone(), amount, moment))?;
+                Some(TransactionState::Normal)
+            }
+            Transaction::Authorize {
+                id,
+                amount,
+                currency,
+                ..
+            } => {
+                let amount = self.authorize(id, amount, currency, config)?;
+                let mut record = TxRecord::new(tx.clone(), amount, moment);
+                record.state = TransactionState::Authorized(amount);
+                storage.put_transaction(id, record)?;
+                Some(TransactionState::Authorized(amount))
+            }
+            Transaction::Capture { id, amount, .. } => {
+                let mut record = self.record(id, storage)?;
+                self.capture(&mut record, amount, config)?;
+                let state = record.state;
+                storage.put_transaction(id, record)?;
+                Some(state)
+            }
+            Transaction::Void { id, .. } => {
+                let mut record = self.record(id, storage)?;
+                self.void(&mut record)?;
+                let state = record.state;
+                storage.put_transaction(id, record)?;
+                Some(state)
+            }
+            Transaction::Dispute { id, amount, .. } => {
+                let mut record = self.record(id, storage)?;
+                self.dispute(&mut record, amount, moment, config)?;
+                let state = record.state;
+                storage.put_transaction(id, record)?;
+                Some(state)
+            }
+            Transaction::Resolve { id, .. } => {
+                let mut record = self.record(id, storage)?;
+                self.resolve(&mut record)?;
+                let state = record.state;
+                storage.put_transaction(id, record)?;
+                Some(state)
+            }
+            Transaction::Chargeback { id, .. } => {
+                let mut record = self.record(id, storage)?;
+                self.chargeback(&mut record, config)?;
+                let state = record.state;
+                storage.put_transaction(id, record)?;
+                Some(state)
+            }
+            Transaction::Unlock { .. } => {
+                self.unlock()?;
+                None
+            }
+            Transaction::Freeze { .. } => {
+                self.freeze()?;
+                None
+            }
+            Transaction::Close { .. } => {
+                self.close()?;
+                None
+            }
+            Transaction::CreditLimit {
+                amount, currency, ..
+            } => {
+                self.set_credit_limit(amount, currency, config)?;
+                None
+            }
+        };
+        self.add_history(tx, state, false, storage, config)
+    }
+
+    /// Processes the receiving side of a transfer, or of a dispute, resolve or chargeback of a
+    /// transfer, on the account of `to`
+    ///
+    /// `record` is the transfer before the transaction, it is only changed by the account of
+    /// `from`. A transfer is only allowed to an active or frozen account, a dispute, resolve or
+    /// chargeback of a transfer is also processed on a locked or closed account.
+    pub(crate) fn process_counterpart<S: Storage + ?Sized>(
+        &mut self,
+        tx: Transaction,
+        record: Option<TxRecord>,
+        storage: &mut S,
+        config: &LedgerConfig,
+    ) -> Result<(), Error> {
+        let moment = self.moment(tx.timestamp());
+        let mut record = match (&tx, record) {
+            (Transaction::Transfer { to, .. }, _) if *to == self.id => None,
+            (_, Some(record)) if record.transaction.counterpart() == Some(self.id) => Some(record),
+            _ => return Err(Error::Unauthorized(tx.client(), self.id)),
+        };
+        let state = match (&tx, &mut record) {
+            (
+                Transaction::Transfer {
+                    id,
+                    amount,
+                    currency,
+                    ..
+                },
+                None,
+            ) => {
+                match self.status {
+                    AccountStatus::Closed => return Err(Error::Closed(self.id)),
+                    AccountStatus::Locked => return Err(Error::Locked(self.id)),
+                    _ => {}
                 }
-                Err(e) => Err(e),
-            },
-            Transaction::Dispute { id, .. } => self.dispute(id),
-            Transaction::Resolve { id, .. } => self.resolve(id),
-            Transaction::Chargeback { id, .. } => self.chargeback(id),
+                self.deposit(*id, *amount, *currency, config)?;
+                TransactionState::Normal
+            }
+            (Transaction::Dispute { amount, .. }, Some(record)) => {
+                self.dispute(record, *amount, moment, config)?;
+                record.state
+            }
+            (Transaction::Resolve { .. }, Some(record)) => {
+                self.resolve(record)?;
+                record.state
+            }
+            (Transaction::Chargeback { .. }, Some(record)) => {
+                self.chargeback(record, config)?;
+                record.state
+            }
+            _ => return Err(Error::Unauthorized(tx.client(), self.id)),
+        };
+        self.add_history(tx, Some(state), true, storage, config)
+    }
+
+    /// Adds a processed transaction to the history of the account
+    fn add_history<S: Storage + ?Sized>(
+        &mut self,
+        tx: Transaction,
+        state: Option<TransactionState>,
+        counterpart: bool,
+        storage: &mut S,
+        config: &LedgerConfig,
+    ) -> Result<(), Error> {
+        self.last_activity = self.last_activity.max(tx.timestamp());
+        *self.counts.entry(tx.kind()).or_default() += 1;
+        // Succeeded, so rounding the amount of the transaction succeeds too
+        let rounded = applied_amount(&tx)
+            .and_then(|amount| Some((amount, rounded(amount, config).ok()?)))
+            .and_then(|(amount, rounded)| (rounded != amount).then_some(rounded));
+        storage.put_history(HistoryEntry {
+            sequence: self.sequence,
+            transaction: tx,
+            state,
+            account: counterpart.then_some(self.id),
+            rounded,
+        })?;
+        self.sequence += 1;
+        Ok(())
+    }
+
+    /// Gets a deposit, withdrawal, transfer or authorization of this account from storage
+    fn record<S: Storage + ?Sized>(&self, id: TxId, storage: &S) -> Result<TxRecord, Error> {
+        match storage.transaction(id)? {
+            Some(record) if record.transaction.client() == self.id => Ok(record),
+            _ => Err(Error::TransactionNotFound(id, self.id)),
         }
     }
 
-    fn deposit(&mut self, id: TxId, mut amount: Decimal) -> Result<(), Error> {
-        if amount <= Decimal::ZERO {
-            return Err(Error::AmountTooLow);
+    /// Adds funds, returns the added amount
+    fn deposit(
+        &mut self,
+        id: TxId,
+        amount: Decimal,
+        currency: Option<Currency>,
+        config: &LedgerConfig,
+    ) -> Result<Decimal, Error> {
+        let amount = checked_amount(amount, config)?;
+        let balance = self.balances.entry(currency).or_default();
+        let before = *balance;
+        if balance.total.checked_add(amount).is_none() {
+            return Err(Error::Overflow(amount, balance.total));
         }
-        if amount.scale() > 4 {
-            amount.rescale(4);
+        match balance.available.checked_add(amount) {
+            Some(amount) => balance.available = amount,
+            None => return Err(Error::Overflow(amount, balance.available)),
         }
-        match self.available.checked_add(amount) {
-            Some(amount) => self.available = amount,
-            None => return Err(Error::Overflow(amount, self.available)),
+        balance.compute_total();
+        let change = self.change(currency, before);
+        self.events.push(Event::Deposited { id, change });
+        Ok(amount)
+    }
+
+    /// Removes funds, returns the removed amount
+    fn withdrawal(
+        &mut self,
+        id: TxId,
+        amount: Decimal,
+        currency: Option<Currency>,
+        config: &LedgerConfig,
+    ) -> Result<Decimal, Error> {
+        let amount = checked_amount(amount, config)?;
+        let before = match self.balances.get_mut(&currency) {
+            Some(balance) if balance.covers(amount) => {
+                let before = *balance;
+                balance.available -= amount;
+                balance.compute_total();
+                before
+            }
+            _ => return Err(Error::InsufficientFunds(self.id)),
+        };
+        let change = self.change(currency, before);
+        self.events.push(Event::Withdrawn { id, change });
+        Ok(amount)
+    }
+
+    /// Holds funds for an authorization, returns the held amount
+    fn authorize(
+        &mut self,
+        id: TxId,
+        amount: Decimal,
+        currency: Option<Currency>,
+        config: &LedgerConfig,
+    ) -> Result<Decimal, Error> {
+        let amount = checked_amount(amount, config)?;
+        let before = match self.balances.get_mut(&currency) {
+            Some(balance) if balance.covers(amount) => {
+                let before = *balance;
+                balance.available -= amount;
+                balance.authorized += amount;
+                before
+            }
+            _ => return Err(Error::InsufficientFunds(self.id)),
+        };
+        self.authorizations.insert(id);
+        let change = self.change(currency, before);
+        self.events.push(Event::Authorized { id, change });
+        Ok(amount)
+    }
+
+    fn capture(
+        &mut self,
+        record: &mut TxRecord,
+        amount: Option<Decimal>,
+        config: &LedgerConfig,
+    ) -> Result<(), Error> {
+        let (id, currency, remaining) = self.authorization(record)?;
+        let amount = match amount {
+            Some(amount) => checked_amount(amount, config)?,
+            None => remaining,
+        };
+        if amount > remaining {
+            return Err(Error::CaptureAmountExceeded(id));
         }
-        self.compute_total();
-        self.tx_state.insert(id, TransactionState::Normal);
+
+        let balance = self.balances.entry(currency).or_default();
+        let before = *balance;
+        balance.authorized -= amount;
+        balance.compute_total();
+        record.state = match remaining - amount {
+            remaining if remaining.is_zero() => {
+                self.authorizations.remove(&id);
+                TransactionState::Captured
+            }
+            remaining => TransactionState::Authorized(remaining),
+        };
+        let change = self.change(currency, before);
+        self.events.push(Event::Captured { id, change });
         Ok(())
     }
 
-    fn withdrawal(&mut self, mut amount: Decimal) -> Result<(), Error> {
-        if amount <= Decimal::ZERO {
-            return Err(Error::AmountTooLow);
+    fn void(&mut self, record: &mut TxRecord) -> Result<(), Error> {
+        let (id, currency, remaining) = self.authorization(record)?;
+
+        let balance = self.balances.entry(currency).or_default();
+        let before = *balance;
+        match balance.available.checked_add(remaining) {
+            Some(available) => balance.available = available,
+            None => return Err(Error::Overflow(remaining, balance.available)),
         }
-        if amount <= self.available {
-            if amount.scale() > 4 {
-                amount.rescale(4);
+        balance.authorized -= remaining;
+        record.state = TransactionState::Voided;
+        self.authorizations.remove(&id);
+        let change = self.change(currency, before);
+        self.events.push(Event::Voided { id, change });
+        Ok(())
+    }
+
+    /// Gets the ID, currency and remaining held amount of an open authorization
+    fn authorization(&self, record: &TxRecord) -> Result<(TxId, Option<Currency>, Decimal), Error> {
+        match (&record.transaction, record.state) {
+            (Transaction::Authorize { id, currency, .. }, TransactionState::Authorized(held)) => {
+                Ok((*id, *currency, held))
             }
-            self.available -= amount;
-            self.compute_total();
-            Ok(())
-        } else {
-            Err(Error::InsufficientFunds(self.id))
+            (tx, _) => Err(Error::NotAuthorized(tx.id())),
         }
     }
 
-    fn dispute(&mut self, id: TxId) -> Result<(), Error> {
-        match self.tx_state.entry(id) {
-            Entry::Vacant(..) => return Err(Error::TransactionNotFound(id, self.id)),
-            Entry::Occupied(mut entry) => match entry.get() {
-                TransactionState::Disputed => return Err(Error::AlreadyDisputed(id)),
-                TransactionState::Chargedback => return Err(Error::AlreadyChargedBack(id)),
-                TransactionState::Normal => {
-                    entry.insert(TransactionState::Disputed);
-                }
-            },
+    fn dispute(
+        &mut self,
+        record: &mut TxRecord,
+        amount: Option<Decimal>,
+        moment: Moment,
+        config: &LedgerConfig,
+    ) -> Result<(), Error> {
+        let id = record.transaction.id();
+        let disputed = match record.state {
+            TransactionState::Chargedback => return Err(Error::AlreadyChargedBack(id)),
+            TransactionState::Normal if record.resolved && !config.redispute() => {
+                return Err(Error::AlreadyDisputed(id))
+            }
+            TransactionState::Disputed(..) if amount.is_none() => {
+                return Err(Error::AlreadyDisputed(id))
+            }
+            TransactionState::Disputed(held) => held,
+            TransactionState::Normal => Decimal::ZERO,
+            // Authorizations cannot be disputed
+            TransactionState::Authorized(..)
+            | TransactionState::Captured
+            | TransactionState::Voided => return Err(Error::TransactionNotFound(id, self.id)),
+        };
+        let (tx_amount, currency, incoming) = self.direction(record)?;
+
+        let amount = match amount {
+            Some(amount) => checked_amount(amount, config)?,
+            None => tx_amount - disputed,
+        };
+        if disputed + amount > tx_amount {
+            return Err(Error::DisputeAmountExceeded(id));
         }
 
-        match self.transactions.get(&id) {
-            Some(tx) => {
-                let amount = tx.amount().unwrap();
-                match self.held.checked_add(amount) {
-                    Some(amount) => self.held = amount,
-                    None => return Err(Error::Overflow(amount, self.held)),
-                }
-                self.available -= amount;
-                Ok(())
+        let balance = self.balances.entry(currency).or_default();
+        let before = *balance;
+        // The total only grows when the funds of a withdrawal are held
+        if !incoming && balance.total.checked_add(amount).is_none() {
+            return Err(Error::Overflow(amount, balance.total));
+        }
+        match balance.held.checked_add(amount) {
+            Some(held) => balance.held = held,
+            None => return Err(Error::Overflow(amount, balance.held)),
+        }
+        if incoming {
+            balance.available -= amount;
+        } else {
+            balance.compute_total();
+        }
+        // Only the account of the client that owns the transaction tracks the dispute
+        if record.state == TransactionState::Normal && record.transaction.client() == self.id {
+            record.disputed = Some(moment);
+            self.disputes.insert(id);
+        }
+        record.state = TransactionState::Disputed(disputed + amount);
+        let change = self.change(currency, before);
+        self.events.push(Event::DisputeOpened { id, change });
+        Ok(())
+    }
+
+    fn resolve(&mut self, record: &mut TxRecord) -> Result<(), Error> {
+        let id = record.transaction.id();
+        let amount = match record.state {
+            TransactionState::Normal
+            | TransactionState::Authorized(..)
+            | TransactionState::Captured
+            | TransactionState::Voided => return Err(Error::NotInDispute(id)),
+            TransactionState::Chargedback => return Err(Error::AlreadyChargedBack(id)),
+            TransactionState::Disputed(held) => held,
+        };
+
+        let (_, currency, incoming) = self.direction(record)?;
+        let balance = self.balances.entry(currency).or_default();
+        let before = *balance;
+        if incoming {
+            match balance.available.checked_add(amount) {
+                Some(amount) => balance.available = amount,
+                None => return Err(Error::Overflow(amount, balance.available)),
             }
-            None => Err(Error::TransactionNotFound(id, self.id)),
+            balance.held -= amount;
+        } else {
+            balance.held -= amount;
+            balance.compute_total();
         }
+        record.state = TransactionState::Normal;
+        record.resolved = true;
+        record.disputed = None;
+        self.disputes.remove(&id);
+        let change = self.change(currency, before);
+        self.events.push(Event::DisputeResolved { id, change });
+        Ok(())
     }
 
-    fn resolve(&mut self, id: TxId) -> Result<(), Error> {
-        match self.tx_state.entry(id) {
-            Entry::Vacant(..) => return Err(Error::TransactionNotFound(id, self.id)),
-            Entry::Occupied(mut entry) => match entry.get() {
-                TransactionState::Normal => return Err(Error::NotInDispute(id)),
-                TransactionState::Chargedback => return Err(Error::AlreadyChargedBack(id)),
-                TransactionState::Disputed => {
-                    entry.insert(TransactionState::Normal);
-                }
-            },
+    fn chargeback(&mut self, record: &mut TxRecord, config: &LedgerConfig) -> Result<(), Error> {
+        let id = record.transaction.id();
+        let amount = match record.state {
+            TransactionState::Normal
+            | TransactionState::Authorized(..)
+            | TransactionState::Captured
+            | TransactionState::Voided => return Err(Error::NotInDispute(id)),
+            TransactionState::Chargedback => return Err(Error::AlreadyChargedBack(id)),
+            TransactionState::Disputed(held) => held,
+        };
+
+        let (_, currency, incoming) = self.direction(record)?;
+        let balance = self.balances.entry(currency).or_default();
+        let before = *balance;
+        if incoming {
+            balance.held -= amount;
+            balance.compute_total();
+        } else {
+            match balance.available.checked_add(amount) {
+                Some(amount) => balance.available = amount,
+                None => return Err(Error::Overflow(amount, balance.available)),
+            }
+            balance.held -= amount;
         }
+        record.state = TransactionState::Chargedback;
+        record.disputed = None;
+        self.disputes.remove(&id);
+        let change = self.change(currency, before);
+        self.events.push(Event::ChargedBack { id, change });
+        if config.lock_on_chargeback() && record.transaction.client() == self.id {
+            self.status = AccountStatus::Locked;
+            self.events.push(Event::AccountLocked { client: self.id });
+        }
+        Ok(())
+    }
 
-        match self.transactions.get(&id) {
-            Some(tx) => {
-                let amount = tx.amount().unwrap();
-                match self.available.checked_add(amount) {
-                    Some(amount) => self.available = amount,
-                    None => return Err(Error::Overflow(amount, self.available)),
-                }
-                self.held -= amount;
-                Ok(())
+    /// Gets the applied amount and currency of a deposit, withdrawal or transfer, and whether
+    /// it added funds to this account
+    fn direction(&self, record: &TxRecord) -> Result<(Decimal, Option<Currency>, bool), Error> {
+        match record.transaction {
+            Transaction::Deposit { currency, .. } => Ok((record.amount, currency, true)),
+            Transaction::Withdrawal { currency, .. } => Ok((record.amount, currency, false)),
+            Transaction::Transfer { to, currency, .. } => {
+                Ok((record.amount, currency, to == self.id))
             }
-            None => Err(Error::TransactionNotFound(id, self.id)),
+            ref tx => Err(Error::TransactionNotFound(tx.id(), self.id)),
         }
     }
 
-    fn chargeback(&mut self, id: TxId) -> Result<(), Error> {
-        match self.tx_state.entry(id) {
-            Entry::Vacant(..) => return Err(Error::TransactionNotFound(id, self.id)),
-            Entry::Occupied(mut entry) => match entry.get() {
-                TransactionState::Normal => return Err(Error::NotInDispute(id)),
-                TransactionState::Chargedback => return Err(Error::AlreadyChargedBack(id)),
-                TransactionState::Disputed => {
-                    entry.insert(TransactionState::Chargedback);
-                }
-            },
+    fn unlock(&mut self) -> Result<(), Error> {
+        self.status = AccountStatus::Active;
+        Ok(())
+    }
+
+    fn freeze(&mut self) -> Result<(), Error> {
+        self.status = AccountStatus::Frozen;
+        Ok(())
+    }
+
+    /// Sets the credit limit in a currency, a limit below the credit in use only blocks further
+    /// withdrawals
+    pub(crate) fn set_credit_limit(
+        &mut self,
+        limit: Decimal,
+        currency: Option<Currency>,
+        config: &LedgerConfig,
+    ) -> Result<(), Error> {
+        if limit < Decimal::ZERO {
+            return Err(Error::AmountTooLow);
         }
+        let limit = rounded(limit, config)?;
+        self.balances.entry(currency).or_default().credit_limit = limit;
+        Ok(())
+    }
 
-        match self.transactions.get(&id) {
-            Some(tx) => {
-                let amount = tx.amount().unwrap();
-                self.held -= amount;
-                self.compute_total();
-                self.locked = true;
-                Ok(())
-            }
-            None => Err(Error::TransactionNotFound(id, self.id)),
+    fn close(&mut self) -> Result<(), Error> {
+        let zero = |balance: &Balance| {
+            balance.held.is_zero() && balance.authorized.is_zero() && balance.total.is_zero()
+        };
+        if !self.balances.values().all(zero) {
+            return Err(Error::NonZeroBalance(self.id));
         }
+        self.status = AccountStatus::Closed;
+        Ok(())
     }
+}
 
-    fn compute_total(&mut self) {
-        self.total = self.available + self.held;
+/// Rounds the amount to the precision of the ledger, and rejects negative amounts and zero
+/// amounts unless allowed
+fn checked_amount(amount: Decimal, config: &LedgerConfig) -> Result<Decimal, Error> {
+    if amount < Decimal::ZERO {
+        return Err(Error::AmountTooLow);
+    }
+    // An amount can round to zero
+    let amount = rounded(amount, config)?;
+    if amount.is_zero() && !config.zero_amounts() {
+        return Err(Error::AmountTooLow);
+    }
+    Ok(amount)
+}
+
+/// Rounds an amount to the precision of the ledger with its rounding mode
+///
+/// Every amount that enters the ledger is rounded here. Trailing zeroes beyond the precision
+/// are not rejected by [`Rounding::Reject`], as they do not change the amount.
+pub(crate) fn rounded(amount: Decimal, config: &LedgerConfig) -> Result<Decimal, Error> {
+    let precision = config.precision();
+    if amount.scale() <= precision {
+        return Ok(amount);
+    }
+    let strategy = match config.rounding() {
+        Rounding::Bankers => RoundingStrategy::MidpointNearestEven,
+        Rounding::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+        Rounding::Truncate => RoundingStrategy::ToZero,
+        Rounding::Reject => {
+            let normalized = amount.normalize();
+            return match normalized.scale() <= precision {
+                true => Ok(normalized),
+                false => Err(Error::PrecisionExceeded(amount, precision)),
+            };
+        }
+    };
+    Ok(amount.round_dp_with_strategy(precision, strategy))
+}
+
+/// Gets the amount a transaction applies to the account, if it has one
+fn applied_amount(tx: &Transaction) -> Option<Decimal> {
+    match *tx {
+        Transaction::Dispute { amount, .. } | Transaction::Capture { amount, .. } => amount,
+        Transaction::CreditLimit { amount, .. } => Some(amount),
+        _ => tx.amount(),
+    }
+}
+
+/// Serializes the balances as a sequence of pairs, the default currency cannot be a map key
+mod balances {
+    use super::Balance;
+    use crate::Currency;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::BTreeMap;
+
+    pub(super) fn serialize<S: Serializer>(
+        balances: &BTreeMap<Option<Currency>, Balance>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(balances)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<Option<Currency>, Balance>, D::Error> {
+        let pairs = Vec::<(Option<Currency>, Balance)>::deserialize(deserializer)?;
+        Ok(pairs.into_iter().collect())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Account;
-    use crate::{error::Error, transaction::TransactionState, Transaction};
+    use super::{Account, AccountStatus};
+    use crate::{
+        error::Error, storage::MemoryStorage, transaction::TransactionState, Event, Id,
+        LedgerConfig, Rounding, Transaction,
+    };
     use rust_decimal::Decimal;
 
     // All success cases
     #[test]
     fn deposit() {
         // Setup
-        let id = 1;
-        let client = 1;
+        let id = Id::from(1);
+        let client = Id::from(1);
         let amount = Decimal::new(2, 0);
         let mut account = Account::new(client);
-        let tx = Transaction::Deposit { id, client, amount };
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+        let tx = Transaction::Deposit {
+            id,
+            client,
+            amount,
+            currency: None,
+            timestamp: None,
+        };
 
         // Act
-        let result = account.process_tx(tx);
+        let result = account.process_tx(tx, &mut storage, &config);
 
         // Assert
         assert!(result.is_ok());
-        assert_eq!(account.available, amount);
-        assert_eq!(account.held, Decimal::ZERO);
-        assert_eq!(account.total, amount);
-        assert!(!account.locked);
-        assert!(account.transactions.contains_key(&id));
-        assert!(account.tx_state.contains_key(&id));
-        let state = account.tx_state.get(&id).unwrap();
+        assert_eq!(account.balance(None).available, amount);
+        assert_eq!(account.balance(None).held, Decimal::ZERO);
+        assert_eq!(account.balance(None).total, amount);
+        assert_eq!(account.status, AccountStatus::Active);
+        assert!(storage.transactions.contains_key(&id));
+        let state = &storage.transactions.get(&id).unwrap().state;
         assert!(matches!(state, TransactionState::Normal));
     }
 
     #[test]
     fn withdrawal() {
         // Setup
-        let id = 1;
-        let client = 1;
+        let id = Id::from(1);
+        let client = Id::from(1);
         let amount = Decimal::new(2, 0);
         let mut account = Account::new(client);
-        let tx = Transaction::Deposit { id, client, amount };
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+        let tx = Transaction::Deposit {
+            id,
+            client,
+            amount,
+            currency: None,
+            timestamp: None,
+        };
 
-        assert!(account.process_tx(tx).is_ok());
+        assert!(account.process_tx(tx, &mut storage, &config).is_ok());
 
-        let id = 2;
-        let tx = Transaction::Withdrawal { id, client, amount };
+        let id = Id::from(2);
+        let tx = Transaction::Withdrawal {
+            id,
+            client,
+            amount,
+            currency: None,
+            timestamp: None,
+        };
         // Act
-        let result = account.process_tx(tx);
+        let result = account.process_tx(tx, &mut storage, &config);
 
         // Assert
         assert!(result.is_ok());
-        assert_eq!(account.available, Decimal::ZERO);
-        assert_eq!(account.held, Decimal::ZERO);
-        assert_eq!(account.total, Decimal::ZERO);
-        assert!(!account.locked);
-        assert!(account.transactions.contains_key(&id));
-        assert!(!account.tx_state.contains_key(&id));
+        assert_eq!(account.balance(None).available, Decimal::ZERO);
+        assert_eq!(account.balance(None).held, Decimal::ZERO);
+        assert_eq!(account.balance(None).total, Decimal::ZERO);
+        assert_eq!(account.status, AccountStatus::Active);
+        assert!(storage.transactions.contains_key(&id));
+        let state = &storage.transactions.get(&id).unwrap().state;
+        assert!(matches!(state, TransactionState::Normal));
     }
 
     #[test]
     fn dispute() {
         // Setup
-        let id = 1;
-        let client = 1;
+        let id = Id::from(1);
+        let client = Id::from(1);
         let amount = Decimal::new(2, 0);
         let mut account = Account::new(client);
-        let tx = Transaction::Deposit { id, client, amount };
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+        let tx = Transaction::Deposit {
+            id,
+            client,
+            amount,
+            currency: None,
+            timestamp: None,
+        };
 
-        assert!(account.process_tx(tx).is_ok());
+        assert!(account.process_tx(tx, &mut storage, &config).is_ok());
 
-        let tx = Transaction::Dispute { id, client };
+        let tx = Transaction::Dispute {
+            id,
+            client,
+            amount: None,
+            timestamp: None,
+        };
         // Act
-        let result = account.process_tx(tx);
+        let result = account.process_tx(tx, &mut storage, &config);
 
         // Assert
         assert!(result.is_ok());
-        assert_eq!(account.available, Decimal::ZERO);
-        assert_eq!(account.held, amount);
-        assert_eq!(account.total, amount);
-        assert!(!account.locked);
-        assert!(account.transactions.contains_key(&id));
-        assert!(account.tx_state.contains_key(&id));
-        let state = account.tx_state.get(&id).unwrap();
-        assert!(matches!(state, TransactionState::Disputed));
+        assert_eq!(account.balance(None).available, Decimal::ZERO);
+        assert_eq!(account.balance(None).held, amount);
+        assert_eq!(account.balance(None).total, amount);
+        assert_eq!(account.status, AccountStatus::Active);
+        assert!(storage.transactions.contains_key(&id));
+        let state = &storage.transactions.get(&id).unwrap().state;
+        assert!(matches!(state, TransactionState::Disputed(..)));
     }
 
     #[test]
     fn resolve() {
         // Setup
-        let id = 1;
-        let client = 1;
+        let id = Id::from(1);
+        let client = Id::from(1);
         let amount = Decimal::new(2, 0);
         let mut account = Account::new(client);
-        let tx = Transaction::Deposit { id, client, amount };
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+        let tx = Transaction::Deposit {
+            id,
+            client,
+            amount,
+            currency: None,
+            timestamp: None,
+        };
 
-        assert!(account.process_tx(tx).is_ok());
+        assert!(account.process_tx(tx, &mut storage, &config).is_ok());
 
         assert!(account
-            .process_tx(Transaction::Dispute { id, client })
+            .process_tx(
+                Transaction::Dispute {
+                    id,
+                    client,
+                    amount: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
             .is_ok());
-        let tx = Transaction::Resolve { id, client };
+        let tx = Transaction::Resolve {
+            id,
+            client,
+            timestamp: None,
+        };
 
         // Act
-        let result = account.process_tx(tx);
+        let result = account.process_tx(tx, &mut storage, &config);
 
         // Assert
         assert!(result.is_ok());
-        assert_eq!(account.available, amount);
-        assert_eq!(account.held, Decimal::ZERO);
-        assert_eq!(account.total, amount);
-        assert!(!account.locked);
-        assert!(account.transactions.contains_key(&id));
-        assert!(account.tx_state.contains_key(&id));
-        let state = account.tx_state.get(&id).unwrap();
+        assert_eq!(account.balance(None).available, amount);
+        assert_eq!(account.balance(None).held, Decimal::ZERO);
+        assert_eq!(account.balance(None).total, amount);
+        assert_eq!(account.status, AccountStatus::Active);
+        assert!(storage.transactions.contains_key(&id));
+        let state = &storage.transactions.get(&id).unwrap().state;
         assert!(matches!(state, TransactionState::Normal));
     }
 
     #[test]
     fn chargeback() {
         // Setup
-        let id = 1;
-        let client = 1;
+        let id = Id::from(1);
+        let client = Id::from(1);
         let amount = Decimal::new(2, 0);
         let mut account = Account::new(client);
-        let tx = Transaction::Deposit { id, client, amount };
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+        let tx = Transaction::Deposit {
+            id,
+            client,
+            amount,
+            currency: None,
+            timestamp: None,
+        };
 
-        assert!(account.process_tx(tx).is_ok());
+        assert!(account.process_tx(tx, &mut storage, &config).is_ok());
 
         assert!(account
-            .process_tx(Transaction::Dispute { id, client })
+            .process_tx(
+                Transaction::Dispute {
+                    id,
+                    client,
+                    amount: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
             .is_ok());
-        let tx = Transaction::Chargeback { id, client };
+        let tx = Transaction::Chargeback {
+            id,
+            client,
+            timestamp: None,
+        };
 
         // Act
-        let result = account.process_tx(tx);
+        let result = account.process_tx(tx, &mut storage, &config);
 
         // Assert
         assert!(result.is_ok());
-        assert_eq!(account.available, Decimal::ZERO);
-        assert_eq!(account.held, Decimal::ZERO);
-        assert_eq!(account.total, Decimal::ZERO);
-        assert!(account.locked);
-        assert!(account.transactions.contains_key(&id));
-        assert!(account.tx_state.contains_key(&id));
-        let state = account.tx_state.get(&id).unwrap();
+        assert_eq!(account.balance(None).available, Decimal::ZERO);
+        assert_eq!(account.balance(None).held, Decimal::ZERO);
+        assert_eq!(account.balance(None).total, Decimal::ZERO);
+        assert_eq!(account.status, AccountStatus::Locked);
+        assert!(storage.transactions.contains_key(&id));
+        let state = &storage.transactions.get(&id).unwrap().state;
         assert!(matches!(state, TransactionState::Chargedback));
     }
 
-    // All error cases
     #[test]
-    fn unauthorized() {
+    fn dispute_withdrawal() {
         // Setup
-        let id = 1;
-        let client = 1;
+        let client = Id::from(1);
         let amount = Decimal::new(2, 0);
         let mut account = Account::new(client);
-        let client = 2;
-        let tx = Transaction::Deposit { id, client, amount };
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+
+        assert!(account
+            .process_tx(
+                Transaction::Deposit {
+                    id: 1.into(),
+                    client,
+                    amount,
+                    currency: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+        let id = Id::from(2);
+        assert!(account
+            .process_tx(
+                Transaction::Withdrawal {
+                    id,
+                    client,
+                    amount,
+                    currency: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+        let tx = Transaction::Dispute {
+            id,
+            client,
+            amount: None,
+            timestamp: None,
+        };
 
         // Act
-        let result = account.process_tx(tx);
+        let result = account.process_tx(tx, &mut storage, &config);
 
         // Assert
-        assert!(matches!(result, Err(Error::Unauthorized(..))));
-        assert_eq!(account.available, Decimal::ZERO);
-        assert_eq!(account.held, Decimal::ZERO);
-        assert_eq!(account.total, Decimal::ZERO);
-        assert!(!account.locked);
-        assert!(!account.transactions.contains_key(&id));
-        assert!(!account.tx_state.contains_key(&id));
+        assert!(result.is_ok());
+        assert_eq!(account.balance(None).available, Decimal::ZERO);
+        assert_eq!(account.balance(None).held, amount);
+        assert_eq!(account.balance(None).total, amount);
+        assert_eq!(account.status, AccountStatus::Active);
+        let state = &storage.transactions.get(&id).unwrap().state;
+        assert!(matches!(state, TransactionState::Disputed(..)));
     }
 
     #[test]
-    fn locked() {
+    fn resolve_withdrawal() {
         // Setup
-        let id = 1;
-        let client = 1;
+        let client = Id::from(1);
         let amount = Decimal::new(2, 0);
         let mut account = Account::new(client);
-        let tx = Transaction::Deposit { id, client, amount };
-
-        assert!(account.process_tx(tx).is_ok());
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
 
         assert!(account
-            .process_tx(Transaction::Dispute { id, client })
+            .process_tx(
+                Transaction::Deposit {
+                    id: 1.into(),
+                    client,
+                    amount,
+                    currency: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
             .is_ok());
-        // lock account
+        let id = Id::from(2);
         assert!(account
-            .process_tx(Transaction::Chargeback { id, client })
+            .process_tx(
+                Transaction::Withdrawal {
+                    id,
+                    client,
+                    amount,
+                    currency: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
             .is_ok());
-
-        let tx = Transaction::Deposit { id, client, amount };
+        assert!(account
+            .process_tx(
+                Transaction::Dispute {
+                    id,
+                    client,
+                    amount: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+        let tx = Transaction::Resolve {
+            id,
+            client,
+            timestamp: None,
+        };
 
         // Act
-        let result = account.process_tx(tx);
+        let result = account.process_tx(tx, &mut storage, &config);
 
         // Assert
-        assert!(matches!(result, Err(Error::Locked(..))));
-        assert_eq!(account.available, Decimal::ZERO);
-        assert_eq!(account.held, Decimal::ZERO);
-        assert_eq!(account.total, Decimal::ZERO);
-        assert!(account.locked);
-        assert!(account.transactions.contains_key(&id));
-        assert!(account.tx_state.contains_key(&id));
-        let state = account.tx_state.get(&id).unwrap();
-        assert!(matches!(state, TransactionState::Chargedback));
+        assert!(result.is_ok());
+        assert_eq!(account.balance(None).available, Decimal::ZERO);
+        assert_eq!(account.balance(None).held, Decimal::ZERO);
+        assert_eq!(account.balance(None).total, Decimal::ZERO);
+        assert_eq!(account.status, AccountStatus::Active);
+        let state = &storage.transactions.get(&id).unwrap().state;
+        assert!(matches!(state, TransactionState::Normal));
     }
 
     #[test]
-    fn amount_too_low() {
+    fn chargeback_withdrawal() {
         // Setup
-        let id = 1;
-        let client = 1;
-        let amount = Decimal::new(0, 0);
+        let client = Id::from(1);
+        let amount = Decimal::new(2, 0);
         let mut account = Account::new(client);
-        let tx = Transaction::Deposit { id, client, amount };
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+
+        assert!(account
+            .process_tx(
+                Transaction::Deposit {
+                    id: 1.into(),
+                    client,
+                    amount,
+                    currency: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+        let id = Id::from(2);
+        assert!(account
+            .process_tx(
+                Transaction::Withdrawal {
+                    id,
+                    client,
+                    amount,
+                    currency: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+        assert!(account
+            .process_tx(
+                Transaction::Dispute {
+                    id,
+                    client,
+                    amount: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+        let tx = Transaction::Chargeback {
+            id,
+            client,
+            timestamp: None,
+        };
 
         // Act
-        let result = account.process_tx(tx);
+        let result = account.process_tx(tx, &mut storage, &config);
 
         // Assert
-        assert!(matches!(result, Err(Error::AmountTooLow)));
-        assert_eq!(account.available, Decimal::ZERO);
-        assert_eq!(account.held, Decimal::ZERO);
-        assert_eq!(account.total, Decimal::ZERO);
-        assert!(!account.locked);
-        assert!(!account.transactions.contains_key(&id));
-        assert!(!account.tx_state.contains_key(&id));
+        assert!(result.is_ok());
+        assert_eq!(account.balance(None).available, amount);
+        assert_eq!(account.balance(None).held, Decimal::ZERO);
+        assert_eq!(account.balance(None).total, amount);
+        assert_eq!(account.status, AccountStatus::Locked);
+        let state = &storage.transactions.get(&id).unwrap().state;
+        assert!(matches!(state, TransactionState::Chargedback));
     }
 
     #[test]
-    fn insufficient_funds() {
+    fn partial_dispute() {
         // Setup
-        let id = 1;
-        let client = 1;
-        let amount = Decimal::new(2, 0);
+        let id = Id::from(1);
+        let client = Id::from(1);
+        let amount = Decimal::new(100, 0);
         let mut account = Account::new(client);
-        let tx = Transaction::Withdrawal { id, client, amount };
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+
+        assert!(account
+            .process_tx(
+                Transaction::Deposit {
+                    id,
+                    client,
+                    amount,
+                    currency: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+        assert!(account
+            .process_tx(
+                Transaction::Dispute {
+                    id,
+                    client,
+                    amount: Some(Decimal::new(30, 0)),
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+        let tx = Transaction::Dispute {
+            id,
+            client,
+            amount: Some(Decimal::new(20, 0)),
+            timestamp: None,
+        };
 
         // Act
-        let result = account.process_tx(tx);
+        let result = account.process_tx(tx, &mut storage, &config);
 
         // Assert
-        assert!(matches!(result, Err(Error::InsufficientFunds(..))));
+        assert!(result.is_ok());
+        assert_eq!(account.balance(None).available, Decimal::new(50, 0));
+        assert_eq!(account.balance(None).held, Decimal::new(50, 0));
+        assert_eq!(account.balance(None).total, amount);
+        let state = &storage.transactions.get(&id).unwrap().state;
+        assert!(matches!(state, TransactionState::Disputed(held) if *held == Decimal::new(50, 0)));
     }
 
     #[test]
-    fn already_disputed() {
+    fn partial_resolve() {
         // Setup
-        let id = 1;
-        let client = 1;
-        let amount = Decimal::new(2, 0);
+        let id = Id::from(1);
+        let client = Id::from(1);
+        let amount = Decimal::new(100, 0);
         let mut account = Account::new(client);
-        let tx = Transaction::Deposit { id, client, amount };
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
 
-        assert!(account.process_tx(tx).is_ok());
         assert!(account
-            .process_tx(Transaction::Dispute { id, client })
+            .process_tx(
+                Transaction::Deposit {
+                    id,
+                    client,
+                    amount,
+                    currency: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
             .is_ok());
-
-        let tx = Transaction::Dispute { id, client };
+        assert!(account
+            .process_tx(
+                Transaction::Dispute {
+                    id,
+                    client,
+                    amount: Some(Decimal::new(30, 0)),
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+        let tx = Transaction::Resolve {
+            id,
+            client,
+            timestamp: None,
+        };
 
         // Act
-        let result = account.process_tx(tx);
+        let result = account.process_tx(tx, &mut storage, &config);
 
         // Assert
-        assert!(matches!(result, Err(Error::AlreadyDisputed(..))));
+        assert!(result.is_ok());
+        assert_eq!(account.balance(None).available, amount);
+        assert_eq!(account.balance(None).held, Decimal::ZERO);
+        assert_eq!(account.balance(None).total, amount);
+        let state = &storage.transactions.get(&id).unwrap().state;
+        assert!(matches!(state, TransactionState::Normal));
     }
 
     #[test]
-    fn transaction_not_found() {
+    fn partial_chargeback() {
         // Setup
-        let id = 1;
-        let client = 1;
+        let id = Id::from(1);
+        let client = Id::from(1);
+        let amount = Decimal::new(100, 0);
         let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
 
-        let tx = Transaction::Dispute { id, client };
+        assert!(account
+            .process_tx(
+                Transaction::Deposit {
+                    id,
+                    client,
+                    amount,
+                    currency: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+        assert!(account
+            .process_tx(
+                Transaction::Dispute {
+                    id,
+                    client,
+                    amount: Some(Decimal::new(30, 0)),
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+        let tx = Transaction::Chargeback {
+            id,
+            client,
+            timestamp: None,
+        };
 
         // Act
-        let result = account.process_tx(tx);
+        let result = account.process_tx(tx, &mut storage, &config);
 
         // Assert
-        assert!(matches!(result, Err(Error::TransactionNotFound(..))));
-
-        let tx = Transaction::Resolve { id, client };
-
-        // Act resolve
-        let result = account.process_tx(tx);
-
-        // Assert
-        assert!(matches!(result, Err(Error::TransactionNotFound(..))));
-
-        let tx = Transaction::Chargeback { id, client };
-        // Act chargeback
-        let result = account.process_tx(tx);
-
-        assert!(matches!(result, Err(Error::TransactionNotFound(..))));
+        assert!(result.is_ok());
+        assert_eq!(account.balance(None).available, Decimal::new(70, 0));
+        assert_eq!(account.balance(None).held, Decimal::ZERO);
+        assert_eq!(account.balance(None).total, Decimal::new(70, 0));
+        assert_eq!(account.status, AccountStatus::Locked);
+        let state = &storage.transactions.get(&id).unwrap().state;
+        assert!(matches!(state, TransactionState::Chargedback));
     }
 
     #[test]
-    fn not_in_dispute() {
+    fn multi_currency() {
         // Setup
-        let id = 1;
-        let client = 1;
+        let client = Id::from(1);
         let amount = Decimal::new(2, 0);
+        let eur = Some("EUR".parse().unwrap());
+        let usd = Some("USD".parse().unwrap());
         let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
 
         assert!(account
-            .process_tx(Transaction::Deposit { id, client, amount })
+            .process_tx(
+                Transaction::Deposit {
+                    id: 1.into(),
+                    client,
+                    amount,
+                    currency: eur,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+        assert!(account
+            .process_tx(
+                Transaction::Deposit {
+                    id: 2.into(),
+                    client,
+                    amount,
+                    currency: usd,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
             .is_ok());
+        let tx = Transaction::Dispute {
+            id: 2.into(),
+            client,
+            amount: None,
+            timestamp: None,
+        };
 
-        let tx = Transaction::Resolve { id, client };
-        // Act resolve
-        let result = account.process_tx(tx);
+        // Act
+        let result = account.process_tx(tx, &mut storage, &config);
 
         // Assert
-        assert!(matches!(result, Err(Error::NotInDispute(..))));
-
-        let tx = Transaction::Chargeback { id, client };
-        // Act chargeback
-        let result = account.process_tx(tx);
+        assert!(result.is_ok());
+        assert_eq!(account.balance(eur).available, amount);
+        assert_eq!(account.balance(eur).held, Decimal::ZERO);
+        assert_eq!(account.balance(usd).available, Decimal::ZERO);
+        assert_eq!(account.balance(usd).held, amount);
+        assert_eq!(account.balance(None).total, Decimal::ZERO);
+        assert_eq!(account.rows().count(), 2);
+    }
 
-        assert!(matches!(result, Err(Error::NotInDispute(..))));
+    fn authorized(amount: Decimal) -> (Account, MemoryStorage) {
+        let client = Id::from(1);
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+        let txs = [
+            Transaction::Deposit {
+                id: 1.into(),
+                client,
+                amount: Decimal::new(10, 0),
+                currency: None,
+                timestamp: None,
+            },
+            Transaction::Authorize {
+                id: 2.into(),
+                client,
+                amount,
+                currency: None,
+                timestamp: None,
+            },
+        ];
+        for tx in txs {
+            assert!(account.process_tx(tx, &mut storage, &config).is_ok());
+        }
+        (account, storage)
     }
 
     #[test]
-    fn overflow() {
+    fn authorize() {
         // Setup
-        let id = 1;
-        let client = 1;
-        let amount = Decimal::MAX;
-        let mut account = Account::new(client);
+        let amount = Decimal::new(4, 0);
 
-        assert!(account
-            .process_tx(Transaction::Deposit { id, client, amount })
+        // Act
+        let (account, storage) = authorized(amount);
+
+        // Assert
+        assert_eq!(account.balance(None).available, Decimal::new(6, 0));
+        assert_eq!(account.balance(None).held, Decimal::ZERO);
+        assert_eq!(account.balance(None).authorized, amount);
+        assert_eq!(account.balance(None).total, Decimal::new(10, 0));
+        assert_eq!(account.open_authorizations().collect::<Vec<_>>(), [2]);
+        assert_eq!(
+            storage.transactions[&2.into()].state,
+            TransactionState::Authorized(amount)
+        );
+    }
+
+    #[test]
+    fn capture() {
+        // Setup
+        let client = Id::from(1);
+        let (mut account, mut storage) = authorized(Decimal::new(4, 0));
+        let config = LedgerConfig::default();
+        let partial = Transaction::Capture {
+            id: 2.into(),
+            client,
+            amount: Some(Decimal::ONE),
+            timestamp: None,
+        };
+        let rest = Transaction::Capture {
+            id: 2.into(),
+            client,
+            amount: None,
+            timestamp: None,
+        };
+
+        // Act
+        let partial = account.process_tx(partial, &mut storage, &config);
+        let remaining = storage.transactions[&2.into()].state;
+        let rest = account.process_tx(rest, &mut storage, &config);
+
+        // Assert
+        assert!(partial.is_ok());
+        assert_eq!(remaining, TransactionState::Authorized(Decimal::new(3, 0)));
+        assert!(rest.is_ok());
+        assert_eq!(
+            storage.transactions[&2.into()].state,
+            TransactionState::Captured
+        );
+        assert_eq!(account.balance(None).available, Decimal::new(6, 0));
+        assert_eq!(account.balance(None).authorized, Decimal::ZERO);
+        assert_eq!(account.balance(None).total, Decimal::new(6, 0));
+        assert!(account.open_authorizations().next().is_none());
+    }
+
+    #[test]
+    fn void() {
+        // Setup
+        let client = Id::from(1);
+        let (mut account, mut storage) = authorized(Decimal::new(4, 0));
+        let config = LedgerConfig::default();
+        let capture = Transaction::Capture {
+            id: 2.into(),
+            client,
+            amount: Some(Decimal::ONE),
+            timestamp: None,
+        };
+        assert!(account.process_tx(capture, &mut storage, &config).is_ok());
+        let tx = Transaction::Void {
+            id: 2.into(),
+            client,
+            timestamp: None,
+        };
+
+        // Act
+        let result = account.process_tx(tx, &mut storage, &config);
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(
+            storage.transactions[&2.into()].state,
+            TransactionState::Voided
+        );
+        assert_eq!(account.balance(None).available, Decimal::new(9, 0));
+        assert_eq!(account.balance(None).authorized, Decimal::ZERO);
+        assert_eq!(account.balance(None).total, Decimal::new(9, 0));
+        assert!(account.open_authorizations().next().is_none());
+    }
+
+    #[test]
+    fn rounding() {
+        // Setup
+        let client = Id::from(1);
+        let deposit = |id, amount| Transaction::Deposit {
+            id,
+            client,
+            amount,
+            currency: None,
+            timestamp: None,
+        };
+        let modes = [
+            (
+                Rounding::Bankers,
+                Decimal::new(20000, 4),
+                Decimal::new(20002, 4),
+            ),
+            (
+                Rounding::HalfUp,
+                Decimal::new(20001, 4),
+                Decimal::new(20002, 4),
+            ),
+            (
+                Rounding::Truncate,
+                Decimal::new(20000, 4),
+                Decimal::new(20001, 4),
+            ),
+        ];
+
+        for (rounding, low, high) in modes {
+            let mut account = Account::new(client);
+            let mut storage = MemoryStorage::default();
+            let config = LedgerConfig::builder().rounding(rounding).build();
+
+            // Act
+            let first = account.process_tx(
+                deposit(1.into(), Decimal::new(200005, 5)),
+                &mut storage,
+                &config,
+            );
+            let available = account.balance(None).available;
+            let second = account.process_tx(
+                deposit(2.into(), Decimal::new(200015, 5)),
+                &mut storage,
+                &config,
+            );
+
+            // Assert
+            assert!(first.is_ok() && second.is_ok());
+            assert_eq!(available, low, "{rounding:?}");
+            assert_eq!(account.balance(None).available, low + high, "{rounding:?}");
+        }
+    }
+
+    #[test]
+    fn withdrawal_rounded() {
+        // Setup
+        let id = Id::from(1);
+        let client = Id::from(1);
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+        let deposit = Transaction::Deposit {
+            id,
+            client,
+            amount: Decimal::new(10001, 4),
+            currency: None,
+            timestamp: None,
+        };
+        assert!(account.process_tx(deposit, &mut storage, &config).is_ok());
+        let tx = Transaction::Withdrawal {
+            id: 2.into(),
+            client,
+            amount: Decimal::new(100005, 5),
+            currency: None,
+            timestamp: None,
+        };
+
+        // Act
+        let result = account.process_tx(tx, &mut storage, &config);
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(account.balance(None).available, Decimal::ZERO);
+        assert_eq!(
+            storage.history[&client][&1].rounded,
+            Some(Decimal::new(10001, 4))
+        );
+        assert_eq!(storage.history[&client][&0].rounded, None);
+    }
+
+    #[test]
+    fn chargeback_rounded() {
+        // Setup
+        let id = Id::from(1);
+        let client = Id::from(1);
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::builder()
+            .precision(2)
+            .rounding(Rounding::Bankers)
+            .build();
+        let deposit = Transaction::Deposit {
+            id,
+            client,
+            amount: Decimal::new(1125, 3),
+            currency: None,
+            timestamp: None,
+        };
+        let dispute = |amount| Transaction::Dispute {
+            id,
+            client,
+            amount,
+            timestamp: None,
+        };
+        assert!(account.process_tx(deposit, &mut storage, &config).is_ok());
+        assert!(account
+            .process_tx(dispute(None), &mut storage, &config)
+            .is_ok());
+        let exceeded = account.process_tx(dispute(Some(Decimal::new(1, 2))), &mut storage, &config);
+        let held = account.balance(None);
+        let tx = Transaction::Chargeback {
+            id,
+            client,
+            timestamp: None,
+        };
+
+        // Act
+        let result = account.process_tx(tx, &mut storage, &config);
+
+        // Assert
+        assert!(matches!(exceeded, Err(Error::DisputeAmountExceeded(..))));
+        assert_eq!(held.available, Decimal::ZERO);
+        assert_eq!(held.held, Decimal::new(112, 2));
+        assert!(result.is_ok());
+        assert_eq!(account.balance(None).available, Decimal::ZERO);
+        assert_eq!(account.balance(None).held, Decimal::ZERO);
+        assert_eq!(account.balance(None).total, Decimal::ZERO);
+    }
+
+    #[test]
+    fn unlock() {
+        // Setup
+        let id = Id::from(1);
+        let client = Id::from(1);
+        let amount = Decimal::new(2, 0);
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+
+        assert!(account
+            .process_tx(
+                Transaction::Deposit {
+                    id,
+                    client,
+                    amount,
+                    currency: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+        assert!(account
+            .process_tx(
+                Transaction::Dispute {
+                    id,
+                    client,
+                    amount: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+        assert!(account
+            .process_tx(
+                Transaction::Chargeback {
+                    id,
+                    client,
+                    timestamp: None
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+        let tx = Transaction::Unlock {
+            id: 2.into(),
+            client,
+            timestamp: None,
+        };
+
+        // Act
+        let result = account.process_tx(tx, &mut storage, &config);
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(account.status, AccountStatus::Active);
+        assert!(account
+            .process_tx(
+                Transaction::Deposit {
+                    id: 3.into(),
+                    client,
+                    amount,
+                    currency: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+        assert_eq!(account.balance(None).available, amount);
+    }
+
+    #[test]
+    fn freeze() {
+        // Setup
+        let id = Id::from(1);
+        let client = Id::from(1);
+        let amount = Decimal::new(2, 0);
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+        let tx = Transaction::Freeze {
+            id,
+            client,
+            timestamp: None,
+        };
+
+        // Act
+        let result = account.process_tx(tx, &mut storage, &config);
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(account.status, AccountStatus::Frozen);
+        assert!(account
+            .process_tx(
+                Transaction::Deposit {
+                    id: 2.into(),
+                    client,
+                    amount,
+                    currency: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+        assert_eq!(account.balance(None).available, amount);
+    }
+
+    #[test]
+    fn close() {
+        // Setup
+        let id = Id::from(1);
+        let client = Id::from(1);
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+        let tx = Transaction::Close {
+            id,
+            client,
+            timestamp: None,
+        };
+
+        // Act
+        let result = account.process_tx(tx, &mut storage, &config);
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(account.status, AccountStatus::Closed);
+    }
+
+    // All error cases
+    #[test]
+    fn unauthorized() {
+        // Setup
+        let id = Id::from(1);
+        let client = Id::from(1);
+        let amount = Decimal::new(2, 0);
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+        let client = Id::from(2);
+        let tx = Transaction::Deposit {
+            id,
+            client,
+            amount,
+            currency: None,
+            timestamp: None,
+        };
+
+        // Act
+        let result = account.process_tx(tx, &mut storage, &config);
+
+        // Assert
+        assert!(matches!(result, Err(Error::Unauthorized(..))));
+        assert_eq!(account.balance(None).available, Decimal::ZERO);
+        assert_eq!(account.balance(None).held, Decimal::ZERO);
+        assert_eq!(account.balance(None).total, Decimal::ZERO);
+        assert_eq!(account.status, AccountStatus::Active);
+        assert!(!storage.transactions.contains_key(&id));
+    }
+
+    #[test]
+    fn locked() {
+        // Setup
+        let id = Id::from(1);
+        let client = Id::from(1);
+        let amount = Decimal::new(2, 0);
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+        let tx = Transaction::Deposit {
+            id,
+            client,
+            amount,
+            currency: None,
+            timestamp: None,
+        };
+
+        assert!(account.process_tx(tx, &mut storage, &config).is_ok());
+
+        assert!(account
+            .process_tx(
+                Transaction::Dispute {
+                    id,
+                    client,
+                    amount: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
             .is_ok());
+        // lock account
+        assert!(account
+            .process_tx(
+                Transaction::Chargeback {
+                    id,
+                    client,
+                    timestamp: None
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+
+        let tx = Transaction::Deposit {
+            id,
+            client,
+            amount,
+            currency: None,
+            timestamp: None,
+        };
+
+        // Act
+        let result = account.process_tx(tx, &mut storage, &config);
 
+        // Assert
+        assert!(matches!(result, Err(Error::Locked(..))));
+        assert_eq!(account.balance(None).available, Decimal::ZERO);
+        assert_eq!(account.balance(None).held, Decimal::ZERO);
+        assert_eq!(account.balance(None).total, Decimal::ZERO);
+        assert_eq!(account.status, AccountStatus::Locked);
+        assert!(storage.transactions.contains_key(&id));
+        let state = &storage.transactions.get(&id).unwrap().state;
+        assert!(matches!(state, TransactionState::Chargedback));
+    }
+
+    #[test]
+    fn amount_too_low() {
+        // Setup
+        let id = Id::from(1);
+        let client = Id::from(1);
+        let amount = Decimal::new(0, 0);
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+        let tx = Transaction::Deposit {
+            id,
+            client,
+            amount,
+            currency: None,
+            timestamp: None,
+        };
+
+        // Act
+        let result = account.process_tx(tx, &mut storage, &config);
+
+        // Assert
+        assert!(matches!(result, Err(Error::AmountTooLow)));
+        assert_eq!(account.balance(None).available, Decimal::ZERO);
+        assert_eq!(account.balance(None).held, Decimal::ZERO);
+        assert_eq!(account.balance(None).total, Decimal::ZERO);
+        assert_eq!(account.status, AccountStatus::Active);
+        assert!(!storage.transactions.contains_key(&id));
+    }
+
+    #[test]
+    fn precision_exceeded() {
+        // Setup
+        let client = Id::from(1);
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::builder()
+            .precision(2)
+            .rounding(Rounding::Reject)
+            .build();
+        let deposit = |id, amount| Transaction::Deposit {
+            id,
+            client,
+            amount,
+            currency: None,
+            timestamp: None,
+        };
+        let credit_limit = Transaction::CreditLimit {
+            id: 3.into(),
+            client,
+            amount: Decimal::new(1001, 3),
+            currency: None,
+            timestamp: None,
+        };
+
+        // Act
+        let exact = account.process_tx(
+            deposit(1.into(), Decimal::new(150000, 5)),
+            &mut storage,
+            &config,
+        );
+        let inexact = account.process_tx(
+            deposit(2.into(), Decimal::new(1501, 3)),
+            &mut storage,
+            &config,
+        );
+        let limit = account.process_tx(credit_limit, &mut storage, &config);
+
+        // Assert
+        assert!(exact.is_ok());
+        assert!(
+            matches!(inexact, Err(Error::PrecisionExceeded(amount, 2)) if amount == Decimal::new(1501, 3))
+        );
+        assert!(matches!(limit, Err(Error::PrecisionExceeded(..))));
+        assert_eq!(account.balance(None).available, Decimal::new(15, 1));
+        assert_eq!(account.balance(None).credit_limit, Decimal::ZERO);
+    }
+
+    #[test]
+    fn insufficient_funds() {
+        // Setup
+        let id = Id::from(1);
+        let client = Id::from(1);
+        let amount = Decimal::new(2, 0);
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+        let tx = Transaction::Withdrawal {
+            id,
+            client,
+            amount,
+            currency: None,
+            timestamp: None,
+        };
+
+        // Act
+        let result = account.process_tx(tx, &mut storage, &config);
+
+        // Assert
+        assert!(matches!(result, Err(Error::InsufficientFunds(..))));
+    }
+
+    #[test]
+    fn already_disputed() {
+        // Setup
+        let id = Id::from(1);
+        let client = Id::from(1);
         let amount = Decimal::new(2, 0);
-        let id = 2;
-        let tx = Transaction::Deposit { id, client, amount };
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+        let tx = Transaction::Deposit {
+            id,
+            client,
+            amount,
+            currency: None,
+            timestamp: None,
+        };
+
+        assert!(account.process_tx(tx, &mut storage, &config).is_ok());
+        assert!(account
+            .process_tx(
+                Transaction::Dispute {
+                    id,
+                    client,
+                    amount: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+
+        let tx = Transaction::Dispute {
+            id,
+            client,
+            amount: None,
+            timestamp: None,
+        };
 
         // Act
-        let result = account.process_tx(tx);
+        let result = account.process_tx(tx, &mut storage, &config);
+
+        // Assert
+        assert!(matches!(result, Err(Error::AlreadyDisputed(..))));
+    }
+
+    #[test]
+    fn transaction_not_found() {
+        // Setup
+        let id = Id::from(1);
+        let client = Id::from(1);
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+
+        let tx = Transaction::Dispute {
+            id,
+            client,
+            amount: None,
+            timestamp: None,
+        };
+
+        // Act
+        let result = account.process_tx(tx, &mut storage, &config);
+
+        // Assert
+        assert!(matches!(result, Err(Error::TransactionNotFound(..))));
+
+        let tx = Transaction::Resolve {
+            id,
+            client,
+            timestamp: None,
+        };
+
+        // Act resolve
+        let result = account.process_tx(tx, &mut storage, &config);
+
+        // Assert
+        assert!(matches!(result, Err(Error::TransactionNotFound(..))));
+
+        let tx = Transaction::Chargeback {
+            id,
+            client,
+            timestamp: None,
+        };
+        // Act chargeback
+        let result = account.process_tx(tx, &mut storage, &config);
+
+        assert!(matches!(result, Err(Error::TransactionNotFound(..))));
+    }
+
+    #[test]
+    fn not_in_dispute() {
+        // Setup
+        let id = Id::from(1);
+        let client = Id::from(1);
+        let amount = Decimal::new(2, 0);
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+
+        assert!(account
+            .process_tx(
+                Transaction::Deposit {
+                    id,
+                    client,
+                    amount,
+                    currency: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+
+        let tx = Transaction::Resolve {
+            id,
+            client,
+            timestamp: None,
+        };
+        // Act resolve
+        let result = account.process_tx(tx, &mut storage, &config);
+
+        // Assert
+        assert!(matches!(result, Err(Error::NotInDispute(..))));
+
+        let tx = Transaction::Chargeback {
+            id,
+            client,
+            timestamp: None,
+        };
+        // Act chargeback
+        let result = account.process_tx(tx, &mut storage, &config);
+
+        assert!(matches!(result, Err(Error::NotInDispute(..))));
+    }
+
+    #[test]
+    fn overflow() {
+        // Setup
+        let id = Id::from(1);
+        let client = Id::from(1);
+        let amount = Decimal::MAX;
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+
+        assert!(account
+            .process_tx(
+                Transaction::Deposit {
+                    id,
+                    client,
+                    amount,
+                    currency: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+
+        let amount = Decimal::new(2, 0);
+        let id = Id::from(2);
+        let tx = Transaction::Deposit {
+            id,
+            client,
+            amount,
+            currency: None,
+            timestamp: None,
+        };
+
+        // Act
+        let result = account.process_tx(tx, &mut storage, &config);
 
         // Assert
         assert!(matches!(result, Err(Error::Overflow(..))));
     }
+
+    #[test]
+    fn overflow_dispute_withdrawal() {
+        // Setup
+        let client = Id::from(1);
+        let amount = Decimal::MAX;
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+        let deposit = |id| Transaction::Deposit {
+            id,
+            client,
+            amount,
+            currency: None,
+            timestamp: None,
+        };
+        let withdrawal = Transaction::Withdrawal {
+            id: 2.into(),
+            client,
+            amount,
+            currency: None,
+            timestamp: None,
+        };
+
+        assert!(account
+            .process_tx(deposit(1.into()), &mut storage, &config)
+            .is_ok());
+        assert!(account
+            .process_tx(withdrawal, &mut storage, &config)
+            .is_ok());
+        assert!(account
+            .process_tx(deposit(3.into()), &mut storage, &config)
+            .is_ok());
+        let tx = Transaction::Dispute {
+            id: 2.into(),
+            client,
+            amount: None,
+            timestamp: None,
+        };
+
+        // Act
+        let result = account.process_tx(tx, &mut storage, &config);
+
+        // Assert
+        assert!(matches!(result, Err(Error::Overflow(..))));
+        assert_eq!(account.balance(None).available, amount);
+        assert_eq!(account.balance(None).held, Decimal::ZERO);
+        assert_eq!(account.balance(None).total, amount);
+    }
+
+    #[test]
+    fn overflow_deposit_disputed() {
+        // Setup
+        let client = Id::from(1);
+        let amount = Decimal::MAX;
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+        let deposit = |id| Transaction::Deposit {
+            id,
+            client,
+            amount,
+            currency: None,
+            timestamp: None,
+        };
+        let dispute = Transaction::Dispute {
+            id: 1.into(),
+            client,
+            amount: None,
+            timestamp: None,
+        };
+
+        assert!(account
+            .process_tx(deposit(1.into()), &mut storage, &config)
+            .is_ok());
+        assert!(account.process_tx(dispute, &mut storage, &config).is_ok());
+
+        // Act
+        let result = account.process_tx(deposit(2.into()), &mut storage, &config);
+
+        // Assert
+        assert!(matches!(result, Err(Error::Overflow(..))));
+        assert_eq!(account.balance(None).available, Decimal::ZERO);
+        assert_eq!(account.balance(None).held, amount);
+    }
+
+    #[test]
+    fn frozen() {
+        // Setup
+        let client = Id::from(1);
+        let amount = Decimal::new(2, 0);
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+
+        assert!(account
+            .process_tx(
+                Transaction::Deposit {
+                    id: 1.into(),
+                    client,
+                    amount,
+                    currency: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+        assert!(account
+            .process_tx(
+                Transaction::Freeze {
+                    id: 2.into(),
+                    client,
+                    timestamp: None
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+        let tx = Transaction::Withdrawal {
+            id: 3.into(),
+            client,
+            amount,
+            currency: None,
+            timestamp: None,
+        };
+
+        // Act
+        let result = account.process_tx(tx, &mut storage, &config);
+
+        // Assert
+        assert!(matches!(result, Err(Error::Frozen(..))));
+        assert_eq!(account.balance(None).available, amount);
+        assert_eq!(account.balance(None).total, amount);
+    }
+
+    #[test]
+    fn closed() {
+        // Setup
+        let client = Id::from(1);
+        let amount = Decimal::new(2, 0);
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+
+        assert!(account
+            .process_tx(
+                Transaction::Close {
+                    id: 1.into(),
+                    client,
+                    timestamp: None
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+
+        // Act
+        let result = account.process_tx(
+            Transaction::Deposit {
+                id: 2.into(),
+                client,
+                amount,
+                currency: None,
+                timestamp: None,
+            },
+            &mut storage,
+            &config,
+        );
+
+        // Assert
+        assert!(matches!(result, Err(Error::Closed(..))));
+
+        // Act unlock
+        let result = account.process_tx(
+            Transaction::Unlock {
+                id: 3.into(),
+                client,
+                timestamp: None,
+            },
+            &mut storage,
+            &config,
+        );
+
+        // Assert
+        assert!(matches!(result, Err(Error::Closed(..))));
+        assert_eq!(account.status, AccountStatus::Closed);
+    }
+
+    #[test]
+    fn non_zero_balance() {
+        // Setup
+        let client = Id::from(1);
+        let amount = Decimal::new(2, 0);
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+
+        assert!(account
+            .process_tx(
+                Transaction::Deposit {
+                    id: 1.into(),
+                    client,
+                    amount,
+                    currency: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+        let tx = Transaction::Close {
+            id: 2.into(),
+            client,
+            timestamp: None,
+        };
+
+        // Act
+        let result = account.process_tx(tx, &mut storage, &config);
+
+        // Assert
+        assert!(matches!(result, Err(Error::NonZeroBalance(..))));
+        assert_eq!(account.status, AccountStatus::Active);
+    }
+
+    #[test]
+    fn dispute_amount_exceeded() {
+        // Setup
+        let id = Id::from(1);
+        let client = Id::from(1);
+        let amount = Decimal::new(100, 0);
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+
+        assert!(account
+            .process_tx(
+                Transaction::Deposit {
+                    id,
+                    client,
+                    amount,
+                    currency: None,
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+        assert!(account
+            .process_tx(
+                Transaction::Dispute {
+                    id,
+                    client,
+                    amount: Some(Decimal::new(80, 0)),
+                    timestamp: None,
+                },
+                &mut storage,
+                &config
+            )
+            .is_ok());
+        let tx = Transaction::Dispute {
+            id,
+            client,
+            amount: Some(Decimal::new(30, 0)),
+            timestamp: None,
+        };
+
+        // Act
+        let result = account.process_tx(tx, &mut storage, &config);
+
+        // Assert
+        assert!(matches!(result, Err(Error::DisputeAmountExceeded(..))));
+        assert_eq!(account.balance(None).available, Decimal::new(20, 0));
+        assert_eq!(account.balance(None).held, Decimal::new(80, 0));
+        assert_eq!(account.balance(None).total, amount);
+    }
+
+    #[test]
+    fn not_authorized() {
+        // Setup
+        let client = Id::from(1);
+        let (mut account, mut storage) = authorized(Decimal::new(4, 0));
+        let config = LedgerConfig::default();
+        let void = Transaction::Void {
+            id: 2.into(),
+            client,
+            timestamp: None,
+        };
+        assert!(account
+            .process_tx(void.clone(), &mut storage, &config)
+            .is_ok());
+        let capture_deposit = Transaction::Capture {
+            id: 1.into(),
+            client,
+            amount: None,
+            timestamp: None,
+        };
+        let dispute = Transaction::Dispute {
+            id: 2.into(),
+            client,
+            amount: None,
+            timestamp: None,
+        };
+
+        // Act
+        let voided = account.process_tx(void, &mut storage, &config);
+        let deposit = account.process_tx(capture_deposit, &mut storage, &config);
+        let dispute = account.process_tx(dispute, &mut storage, &config);
+
+        // Assert
+        assert!(matches!(voided, Err(Error::NotAuthorized(id)) if id == 2));
+        assert!(matches!(deposit, Err(Error::NotAuthorized(id)) if id == 1));
+        assert!(
+            matches!(dispute, Err(Error::TransactionNotFound(id, client)) if id == 2 && client == 1)
+        );
+        assert_eq!(account.balance(None).available, Decimal::new(10, 0));
+        assert_eq!(account.balance(None).held, Decimal::ZERO);
+    }
+
+    #[test]
+    fn capture_amount_exceeded() {
+        // Setup
+        let (mut account, mut storage) = authorized(Decimal::new(4, 0));
+        let config = LedgerConfig::default();
+        let tx = Transaction::Capture {
+            id: 2.into(),
+            client: 1.into(),
+            amount: Some(Decimal::new(5, 0)),
+            timestamp: None,
+        };
+
+        // Act
+        let result = account.process_tx(tx, &mut storage, &config);
+
+        // Assert
+        assert!(matches!(result, Err(Error::CaptureAmountExceeded(id)) if id == 2));
+        assert_eq!(account.balance(None).authorized, Decimal::new(4, 0));
+        assert_eq!(account.balance(None).total, Decimal::new(10, 0));
+    }
+
+    #[test]
+    fn credit_limit() {
+        // Setup
+        let client = Id::from(1);
+        let limit = Decimal::new(100, 0);
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+        let tx = Transaction::CreditLimit {
+            id: 1.into(),
+            client,
+            amount: limit,
+            currency: None,
+            timestamp: None,
+        };
+        assert!(account.process_tx(tx, &mut storage, &config).is_ok());
+
+        // Act
+        let within = account.process_tx(
+            Transaction::Withdrawal {
+                id: 2.into(),
+                client,
+                amount: Decimal::new(60, 0),
+                currency: None,
+                timestamp: None,
+            },
+            &mut storage,
+            &config,
+        );
+        let beyond = account.process_tx(
+            Transaction::Withdrawal {
+                id: 3.into(),
+                client,
+                amount: Decimal::new(41, 0),
+                currency: None,
+                timestamp: None,
+            },
+            &mut storage,
+            &config,
+        );
+
+        // Assert
+        assert!(within.is_ok());
+        assert!(matches!(beyond, Err(Error::InsufficientFunds(..))));
+        let balance = account.balance(None);
+        assert_eq!(balance.available, Decimal::new(-60, 0));
+        assert_eq!(balance.total, Decimal::new(-60, 0));
+        assert_eq!(balance.credit_limit(), limit);
+        assert_eq!(balance.credit_used(), Decimal::new(60, 0));
+    }
+
+    #[test]
+    fn credit_limit_max() {
+        // Setup
+        let client = Id::from(1);
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+        let txs = [
+            Transaction::Deposit {
+                id: 1.into(),
+                client,
+                amount: Decimal::ONE,
+                currency: None,
+                timestamp: None,
+            },
+            Transaction::CreditLimit {
+                id: 2.into(),
+                client,
+                amount: Decimal::MAX,
+                currency: None,
+                timestamp: None,
+            },
+        ];
+        for tx in txs {
+            assert!(account.process_tx(tx, &mut storage, &config).is_ok());
+        }
+
+        // Act
+        let withdrawn = account.process_tx(
+            Transaction::Withdrawal {
+                id: 3.into(),
+                client,
+                amount: Decimal::new(3, 0),
+                currency: None,
+                timestamp: None,
+            },
+            &mut storage,
+            &config,
+        );
+        let authorized = account.process_tx(
+            Transaction::Authorize {
+                id: 4.into(),
+                client,
+                amount: Decimal::ONE,
+                currency: None,
+                timestamp: None,
+            },
+            &mut storage,
+            &config,
+        );
+
+        // Assert
+        assert!(withdrawn.is_ok());
+        assert!(authorized.is_ok());
+        let balance = account.balance(None);
+        assert_eq!(balance.available, Decimal::new(-3, 0));
+        assert_eq!(balance.authorized, Decimal::ONE);
+    }
+
+    #[test]
+    fn negative_credit_limit() {
+        // Setup
+        let client = Id::from(1);
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+        account.status = AccountStatus::Locked;
+        let tx = Transaction::CreditLimit {
+            id: 1.into(),
+            client,
+            amount: Decimal::new(-1, 0),
+            currency: None,
+            timestamp: None,
+        };
+
+        // Act
+        let result = account.process_tx(tx, &mut storage, &config);
+
+        // Assert
+        assert!(matches!(result, Err(Error::AmountTooLow)));
+        assert_eq!(account.balance(None).credit_limit(), Decimal::ZERO);
+    }
+
+    #[test]
+    fn events() {
+        // Setup
+        let client = Id::from(1);
+        let id = Id::from(1);
+        let amount = Decimal::new(2, 0);
+        let mut account = Account::new(client);
+        let mut storage = MemoryStorage::default();
+        let config = LedgerConfig::default();
+        let txs = [
+            Transaction::Deposit {
+                id,
+                client,
+                amount,
+                currency: None,
+                timestamp: None,
+            },
+            Transaction::Dispute {
+                id,
+                client,
+                amount: None,
+                timestamp: None,
+            },
+            Transaction::Chargeback {
+                id,
+                client,
+                timestamp: None,
+            },
+        ];
+
+        // Act
+        for tx in txs {
+            assert!(account.process_tx(tx, &mut storage, &config).is_ok());
+        }
+        let events = account.take_events();
+
+        // Assert
+        assert_eq!(events.len(), 4);
+        match events[0] {
+            Event::Deposited { id, change } if id == 1 => {
+                assert_eq!(change.client, client);
+                assert_eq!(change.before.total, Decimal::ZERO);
+                assert_eq!(change.after.available, amount);
+            }
+            _ => panic!("unexpected event {:?}", events[0]),
+        }
+        match events[1] {
+            Event::DisputeOpened { id, change } if id == 1 => {
+                assert_eq!(change.before.held, Decimal::ZERO);
+                assert_eq!(change.after.held, amount);
+                assert_eq!(change.after.available, Decimal::ZERO);
+            }
+            _ => panic!("unexpected event {:?}", events[1]),
+        }
+        match events[2] {
+            Event::ChargedBack { id, change } if id == 1 => {
+                assert_eq!(change.before.total, amount);
+                assert_eq!(change.after.total, Decimal::ZERO);
+            }
+            _ => panic!("unexpected event {:?}", events[2]),
+        }
+        assert!(matches!(events[3], Event::AccountLocked { client } if client == 1));
+        assert!(account.take_events().is_empty());
+    }
 }
@@ -0,0 +1,189 @@
+use crate::{
+    error::Error,
+    storage::{MemoryStorage, Storage},
+    Account, Ledger, Outcome, Transaction,
+};
+use futures::{Stream, StreamExt};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Async front-end of a [`Ledger`], enabled with the `async` feature
+///
+/// The ledger is shared behind a lock, so it can be cloned and queried while a stream of
+/// transactions is being processed.
+///
+/// # Example
+/// ```rust
+/// use futures::{stream, StreamExt};
+/// use ledger_rs::{AsyncLedger, Ledger, Transaction};
+/// use rust_decimal::Decimal;
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let ledger = AsyncLedger::new(Ledger::new());
+/// let txs = stream::iter((1..=3u64).map(|id| Transaction::Deposit {
+///     id: id.into(),
+///     client: 1.into(),
+///     amount: Decimal::new(2, 0),
+///     currency: None,
+///     timestamp: None,
+/// }));
+///
+/// let results = ledger.process_stream(txs).collect::<Vec<_>>().await;
+///
+/// assert!(results.iter().all(|result| result.is_ok()));
+/// assert_eq!(ledger.get_accounts().await.unwrap().len(), 1);
+/// # });
+/// ```
+pub struct AsyncLedger<S: Storage = MemoryStorage> {
+    ledger: Arc<RwLock<Ledger<S>>>,
+}
+
+impl<S: Storage> Clone for AsyncLedger<S> {
+    fn clone(&self) -> Self {
+        Self {
+            ledger: Arc::clone(&self.ledger),
+        }
+    }
+}
+
+impl<S: Storage> AsyncLedger<S> {
+    pub fn new(ledger: Ledger<S>) -> Self {
+        Self {
+            ledger: Arc::new(RwLock::new(ledger)),
+        }
+    }
+
+    /// Processes a single transaction, see [`Ledger::process_tx`]
+    ///
+    /// # Errors
+    /// Could return an error, see [`Error`] for more
+    pub async fn process_tx(&self, tx: Transaction) -> Result<Outcome, Error> {
+        self.ledger.write().await.process_tx(tx)
+    }
+
+    /// Processes a stream of transactions in order, yielding the outcome of every transaction
+    ///
+    /// A transaction is only processed when the returned stream is polled. The lock is released
+    /// between transactions, so queries are not blocked for the lifetime of the stream.
+    pub fn process_stream<St>(&self, txs: St) -> impl Stream<Item = Result<Outcome, Error>>
+    where
+        St: Stream<Item = Transaction>,
+    {
+        let ledger = self.clone();
+        txs.then(move |tx| {
+            let ledger = ledger.clone();
+            async move { ledger.process_tx(tx).await }
+        })
+    }
+
+    /// Gets all accounts
+    ///
+    /// # Errors
+    /// Returns [`Error::Storage`] if the storage backend fails
+    pub async fn get_accounts(&self) -> Result<Vec<Account>, Error> {
+        self.ledger.read().await.get_accounts().collect()
+    }
+
+    /// Runs a query on the ledger, while holding a read lock
+    pub async fn read<T, F: FnOnce(&Ledger<S>) -> T>(&self, query: F) -> T {
+        query(&*self.ledger.read().await)
+    }
+
+    /// Consumes the front-end, returning the ledger if there are no other clones left
+    pub fn into_inner(self) -> Result<Ledger<S>, Self> {
+        match Arc::try_unwrap(self.ledger) {
+            Ok(ledger) => Ok(ledger.into_inner()),
+            Err(ledger) => Err(Self { ledger }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AsyncLedger;
+    use crate::{error::Error, Account, Ledger, Outcome, Transaction};
+    use futures::{stream, StreamExt};
+    use rust_decimal::Decimal;
+
+    fn transactions() -> Vec<Transaction> {
+        let amount = Decimal::new(2, 0);
+        vec![
+            Transaction::Deposit {
+                id: 1.into(),
+                client: 1.into(),
+                amount,
+                currency: None,
+                timestamp: None,
+            },
+            Transaction::Deposit {
+                id: 1.into(),
+                client: 2.into(),
+                amount,
+                currency: None,
+                timestamp: None,
+            },
+            Transaction::Withdrawal {
+                id: 2.into(),
+                client: 1.into(),
+                amount: Decimal::new(3, 0),
+                currency: None,
+                timestamp: None,
+            },
+            Transaction::Dispute {
+                id: 1.into(),
+                client: 1.into(),
+                amount: None,
+                timestamp: None,
+            },
+        ]
+    }
+
+    #[tokio::test]
+    async fn same_as_sync() {
+        // Setup
+        let mut sync = Ledger::new();
+        let expected = transactions()
+            .into_iter()
+            .map(|tx| sync.process_tx(tx).map_err(|e| e.to_string()))
+            .collect::<Vec<_>>();
+        let ledger = AsyncLedger::new(Ledger::new());
+
+        // Act
+        let results = ledger
+            .process_stream(stream::iter(transactions()))
+            .map(|result| result.map_err(|e| e.to_string()))
+            .collect::<Vec<_>>()
+            .await;
+
+        // Assert
+        assert_eq!(results, expected);
+        let mut accounts = ledger.get_accounts().await.unwrap();
+        accounts.sort_by_key(|account| account.id);
+        let mut expected = sync.get_accounts().map(Result::unwrap).collect::<Vec<_>>();
+        expected.sort_by_key(|account| account.id);
+        let rows = |accounts: Vec<Account>| {
+            accounts
+                .iter()
+                .flat_map(|account| account.rows().collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(rows(accounts), rows(expected));
+    }
+
+    #[tokio::test]
+    async fn query_while_processing() {
+        // Setup
+        let ledger = AsyncLedger::new(Ledger::new());
+        let mut results = Box::pin(ledger.process_stream(stream::iter(transactions())));
+
+        // Act
+        let first = results.next().await;
+        let accounts = ledger.get_accounts().await;
+        let second = results.next().await;
+
+        // Assert
+        assert!(matches!(first, Some(Ok(Outcome::Processed))));
+        assert_eq!(accounts.unwrap().len(), 1);
+        assert!(matches!(second, Some(Err(Error::ConflictingTxId { .. }))));
+    }
+}
@@ -0,0 +1,290 @@
+use crate::{DisputePolicy, Period};
+use serde::Deserialize;
+
+/// How [`Ledger::process_tx`](crate::Ledger::process_tx) treats the timestamps of transactions
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimestampPolicy {
+    /// Timestamps are only recorded
+    #[default]
+    Ignore,
+    /// Transactions with a timestamp before the last activity of their account are rejected
+    /// with [`Error::OutOfOrder`](crate::error::Error::OutOfOrder), transactions without a
+    /// timestamp are always accepted
+    RejectOutOfOrder,
+}
+
+/// How amounts with more decimal places than the precision of a [`LedgerConfig`] are treated
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rounding {
+    /// Round to the nearest, a midpoint rounds to the even neighbour
+    Bankers,
+    /// Round to the nearest, a midpoint rounds away from zero
+    #[default]
+    HalfUp,
+    /// Drop the extra decimal places
+    Truncate,
+    /// Reject the transaction with
+    /// [`Error::PrecisionExceeded`](crate::error::Error::PrecisionExceeded)
+    Reject,
+}
+
+/// Rules of a [`Ledger`](crate::Ledger), created with [`LedgerConfig::builder`]
+///
+/// The default configuration gives the behaviour described in the README. A configuration can
+/// also be deserialized, all fields are optional and default to the default configuration.
+///
+/// # Example
+/// ```rust
+/// use ledger_rs::{Ledger, LedgerConfig};
+///
+/// let config = LedgerConfig::builder()
+///     .precision(2)
+///     .lock_on_chargeback(false)
+///     .build();
+/// let ledger = Ledger::with_config(config);
+///
+/// assert_eq!(ledger.config().precision(), 2);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LedgerConfig {
+    precision: u32,
+    rounding: Rounding,
+    zero_amounts: bool,
+    create_failed_accounts: bool,
+    lock_on_chargeback: bool,
+    unique_tx_ids: bool,
+    reserve_failed_tx_ids: bool,
+    redispute: bool,
+    timestamps: TimestampPolicy,
+    disputes: DisputePolicy,
+    authorization_expiry: Period,
+}
+
+impl Default for LedgerConfig {
+    fn default() -> Self {
+        Self {
+            precision: 4,
+            rounding: Rounding::default(),
+            zero_amounts: false,
+            create_failed_accounts: true,
+            lock_on_chargeback: true,
+            unique_tx_ids: true,
+            reserve_failed_tx_ids: true,
+            redispute: true,
+            timestamps: TimestampPolicy::default(),
+            disputes: DisputePolicy::default(),
+            authorization_expiry: Period::default(),
+        }
+    }
+}
+
+impl LedgerConfig {
+    /// Creates a builder starting from the default configuration
+    pub fn builder() -> LedgerConfigBuilder {
+        LedgerConfigBuilder(Self::default())
+    }
+
+    /// Maximum number of decimal places of amounts, default 4
+    pub fn precision(&self) -> u32 {
+        self.precision
+    }
+
+    /// How amounts with more decimal places than the precision are treated, default
+    /// [`Rounding::HalfUp`]
+    pub fn rounding(&self) -> Rounding {
+        self.rounding
+    }
+
+    /// Whether zero amounts are accepted, default `false`
+    pub fn zero_amounts(&self) -> bool {
+        self.zero_amounts
+    }
+
+    /// Whether a failed transaction creates the account of its client, default `true`
+    pub fn create_failed_accounts(&self) -> bool {
+        self.create_failed_accounts
+    }
+
+    /// Whether a chargeback locks the account, default `true`
+    pub fn lock_on_chargeback(&self) -> bool {
+        self.lock_on_chargeback
+    }
+
+    /// Whether deposit and withdrawal IDs must be globally unique, default `true`
+    pub fn unique_tx_ids(&self) -> bool {
+        self.unique_tx_ids
+    }
+
+    /// Whether a failed deposit or withdrawal uses up its ID, default `true`
+    pub fn reserve_failed_tx_ids(&self) -> bool {
+        self.reserve_failed_tx_ids
+    }
+
+    /// Whether a transaction can be disputed again after a resolve, default `true`
+    pub fn redispute(&self) -> bool {
+        self.redispute
+    }
+
+    pub fn timestamps(&self) -> TimestampPolicy {
+        self.timestamps
+    }
+
+    pub fn disputes(&self) -> DisputePolicy {
+        self.disputes
+    }
+
+    /// Period after an authorization after which it is voided, default unlimited
+    pub fn authorization_expiry(&self) -> Period {
+        self.authorization_expiry
+    }
+}
+
+/// Builder of a [`LedgerConfig`]
+#[derive(Clone, Debug)]
+pub struct LedgerConfigBuilder(LedgerConfig);
+
+impl From<LedgerConfig> for LedgerConfigBuilder {
+    /// Creates a builder starting from an existing configuration
+    fn from(config: LedgerConfig) -> Self {
+        Self(config)
+    }
+}
+
+impl LedgerConfigBuilder {
+    /// Sets the maximum number of decimal places of amounts, more decimal places are treated
+    /// according to the rounding mode
+    pub fn precision(mut self, precision: u32) -> Self {
+        self.0.precision = precision;
+        self
+    }
+
+    /// Sets how amounts with more decimal places than the precision are treated
+    pub fn rounding(mut self, rounding: Rounding) -> Self {
+        self.0.rounding = rounding;
+        self
+    }
+
+    /// Accepts deposits, withdrawals and disputes of zero, negative amounts are always rejected
+    pub fn zero_amounts(mut self, allow: bool) -> Self {
+        self.0.zero_amounts = allow;
+        self
+    }
+
+    /// Creates the account of a client when its transaction fails
+    pub fn create_failed_accounts(mut self, create: bool) -> Self {
+        self.0.create_failed_accounts = create;
+        self
+    }
+
+    /// Locks the account on a chargeback
+    pub fn lock_on_chargeback(mut self, lock: bool) -> Self {
+        self.0.lock_on_chargeback = lock;
+        self
+    }
+
+    /// Rejects deposits and withdrawals with a used ID
+    ///
+    /// Without unique IDs, a dispute, resolve or chargeback refers to the latest deposit or
+    /// withdrawal with the ID, as only the latest transaction with an ID is kept. An ID can only
+    /// be reused by the same client, and not while the earlier transaction is disputed or is an
+    /// open authorization, otherwise the transaction fails with
+    /// [`Error::ConflictingTxId`](crate::error::Error::ConflictingTxId). IDs should still be
+    /// unique across clients for a [`ParallelLedger`](crate::ParallelLedger), which only sees
+    /// the transactions of other clients once it is finished.
+    pub fn unique_tx_ids(mut self, unique: bool) -> Self {
+        self.0.unique_tx_ids = unique;
+        self
+    }
+
+    /// Keeps the ID of a failed deposit or withdrawal in use, so a retry with the ID fails
+    ///
+    /// Without it, an ID is only used once a deposit or withdrawal with the ID succeeds, e.g. a
+    /// withdrawal that failed for insufficient funds can be retried after a deposit. Only
+    /// applies with unique IDs.
+    pub fn reserve_failed_tx_ids(mut self, reserve: bool) -> Self {
+        self.0.reserve_failed_tx_ids = reserve;
+        self
+    }
+
+    /// Allows disputing a transaction again after its dispute was resolved
+    pub fn redispute(mut self, allow: bool) -> Self {
+        self.0.redispute = allow;
+        self
+    }
+
+    pub fn timestamps(mut self, policy: TimestampPolicy) -> Self {
+        self.0.timestamps = policy;
+        self
+    }
+
+    pub fn disputes(mut self, policy: DisputePolicy) -> Self {
+        self.0.disputes = policy;
+        self
+    }
+
+    /// Voids open authorizations once the period after the authorization has ended
+    pub fn authorization_expiry(mut self, period: Period) -> Self {
+        self.0.authorization_expiry = period;
+        self
+    }
+
+    pub fn build(self) -> LedgerConfig {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LedgerConfig, Rounding, TimestampPolicy};
+    use crate::{DisputeExpiry, DisputePolicy, ExpiryAction, Period};
+
+    #[test]
+    fn deserialize() {
+        // Setup
+        let toml = r#"
+            precision = 2
+            rounding = "bankers"
+            lock_on_chargeback = false
+            reserve_failed_tx_ids = false
+            timestamps = "reject-out-of-order"
+            authorization_expiry = { transactions = 10 }
+
+            [disputes]
+            window = { transactions = 100 }
+            expiry = { after = { time = 3600 }, action = "chargeback" }
+        "#;
+
+        // Act
+        let result = toml::from_str::<LedgerConfig>(toml);
+
+        // Assert
+        let expected = LedgerConfig::builder()
+            .precision(2)
+            .rounding(Rounding::Bankers)
+            .lock_on_chargeback(false)
+            .reserve_failed_tx_ids(false)
+            .timestamps(TimestampPolicy::RejectOutOfOrder)
+            .authorization_expiry(Period::Transactions(10))
+            .disputes(DisputePolicy {
+                window: Period::Transactions(100),
+                expiry: Some(DisputeExpiry {
+                    after: Period::Time(3600),
+                    action: ExpiryAction::Chargeback,
+                }),
+            })
+            .build();
+        assert_eq!(result.unwrap(), expected);
+    }
+
+    #[test]
+    fn deserialize_unknown_field() {
+        // Act
+        let result = toml::from_str::<LedgerConfig>("precison = 2");
+
+        // Assert
+        assert!(result.is_err());
+    }
+}
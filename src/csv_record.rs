@@ -1,16 +1,36 @@
-use ledger_rs::{ClientId, Transaction, TxId};
+use csv::ErrorKind;
+use ledger_rs::{error, ClientId, Currency, Id, Timestamp, Transaction, TxId};
 use rust_decimal::Decimal;
-use serde::Deserialize;
+use serde::{de, Deserialize, Deserializer};
+use std::borrow::Cow;
 use thiserror::Error;
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct TransactionRecord {
     #[serde(rename(deserialize = "type"))]
     pub tx_type: TxType,
+    #[serde(deserialize_with = "id")]
     pub client: ClientId,
+    #[serde(deserialize_with = "id")]
     pub tx: TxId,
     #[serde(deserialize_with = "csv::invalid_option")]
     pub amount: Option<Decimal>,
+    #[serde(default)]
+    pub currency: Option<Currency>,
+    #[serde(default)]
+    pub timestamp: Option<Timestamp>,
+    #[serde(default, deserialize_with = "optional_id")]
+    pub to_client: Option<ClientId>,
+}
+
+/// Row of the client settings file
+#[derive(Debug, Deserialize)]
+pub(crate) struct ClientRecord {
+    #[serde(deserialize_with = "id")]
+    pub client: ClientId,
+    #[serde(default)]
+    pub currency: Option<Currency>,
+    pub credit_limit: Decimal,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -18,32 +38,174 @@ pub(crate) struct TransactionRecord {
 pub(crate) enum TxType {
     Deposit,
     Withdrawal,
+    Transfer,
+    Authorize,
+    Capture,
+    Void,
     Dispute,
     Resolve,
     Chargeback,
+    Unlock,
+    Freeze,
+    Close,
+    #[serde(rename = "credit_limit")]
+    CreditLimit,
+}
+
+/// Parses a client or transaction ID as it was written, see [`Id`]
+///
+/// The field is read as a string, so an ID that looks like a number keeps its form.
+fn id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Id, D::Error> {
+    let id = Cow::<str>::deserialize(deserializer)?;
+    id.parse().map_err(de::Error::custom)
+}
+
+/// Parses an optional ID, an empty field has no ID
+fn optional_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Id>, D::Error> {
+    match Option::<Cow<str>>::deserialize(deserializer)? {
+        Some(id) if !id.is_empty() => id.parse().map(Some).map_err(de::Error::custom),
+        _ => Ok(None),
+    }
 }
 
 #[derive(Debug, Error)]
-#[error("Missing amount")]
-pub struct RecordError;
+pub enum RecordError {
+    #[error("Missing amount")]
+    MissingAmount,
+    #[error("Missing receiving client")]
+    MissingToClient,
+}
+
+/// Reason a record of the input file failed
+#[derive(Debug, Error)]
+pub(crate) enum RecordFailure {
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+    #[error(transparent)]
+    Record(#[from] RecordError),
+    #[error(transparent)]
+    Ledger(#[from] error::Error),
+}
+
+impl RecordFailure {
+    /// Stable machine-readable code of the failure, in snake case
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Csv(e) => match e.kind() {
+                ErrorKind::Utf8 { .. } => "invalid_utf8",
+                ErrorKind::Deserialize { .. } => "invalid_field",
+                ErrorKind::Io(..) => "io",
+                _ => "invalid_record",
+            },
+            Self::Record(RecordError::MissingAmount) => "missing_amount",
+            Self::Record(RecordError::MissingToClient) => "missing_to_client",
+            Self::Ledger(e) => e.code(),
+        }
+    }
+}
 
 impl TryFrom<TransactionRecord> for Transaction {
     type Error = RecordError;
     fn try_from(record: TransactionRecord) -> Result<Self, Self::Error> {
         let id = record.tx;
         let client = record.client;
+        let currency = record.currency;
+        let timestamp = record.timestamp;
         match record.tx_type {
             TxType::Deposit => match record.amount {
-                Some(amount) => Ok(Self::Deposit { id, client, amount }),
-                None => Err(RecordError),
+                Some(amount) => Ok(Self::Deposit {
+                    id,
+                    client,
+                    amount,
+                    currency,
+                    timestamp,
+                }),
+                None => Err(RecordError::MissingAmount),
             },
             TxType::Withdrawal => match record.amount {
-                Some(amount) => Ok(Self::Withdrawal { id, client, amount }),
-                None => Err(RecordError),
+                Some(amount) => Ok(Self::Withdrawal {
+                    id,
+                    client,
+                    amount,
+                    currency,
+                    timestamp,
+                }),
+                None => Err(RecordError::MissingAmount),
+            },
+            TxType::Transfer => match (record.amount, record.to_client) {
+                (Some(amount), Some(to)) => Ok(Self::Transfer {
+                    id,
+                    from: client,
+                    to,
+                    amount,
+                    currency,
+                    timestamp,
+                }),
+                (None, _) => Err(RecordError::MissingAmount),
+                (_, None) => Err(RecordError::MissingToClient),
+            },
+            TxType::Authorize => match record.amount {
+                Some(amount) => Ok(Self::Authorize {
+                    id,
+                    client,
+                    amount,
+                    currency,
+                    timestamp,
+                }),
+                None => Err(RecordError::MissingAmount),
+            },
+            TxType::Capture => Ok(Self::Capture {
+                id,
+                client,
+                amount: record.amount,
+                timestamp,
+            }),
+            TxType::Void => Ok(Self::Void {
+                id,
+                client,
+                timestamp,
+            }),
+            TxType::Dispute => Ok(Self::Dispute {
+                id,
+                client,
+                amount: record.amount,
+                timestamp,
+            }),
+            TxType::Resolve => Ok(Self::Resolve {
+                id,
+                client,
+                timestamp,
+            }),
+            TxType::Chargeback => Ok(Self::Chargeback {
+                id,
+                client,
+                timestamp,
+            }),
+            TxType::Unlock => Ok(Self::Unlock {
+                id,
+                client,
+                timestamp,
+            }),
+            TxType::Freeze => Ok(Self::Freeze {
+                id,
+                client,
+                timestamp,
+            }),
+            TxType::Close => Ok(Self::Close {
+                id,
+                client,
+                timestamp,
+            }),
+            TxType::CreditLimit => match record.amount {
+                Some(amount) => Ok(Self::CreditLimit {
+                    id,
+                    client,
+                    amount,
+                    currency,
+                    timestamp,
+                }),
+                None => Err(RecordError::MissingAmount),
             },
-            TxType::Dispute => Ok(Self::Dispute { id, client }),
-            TxType::Resolve => Ok(Self::Resolve { id, client }),
-            TxType::Chargeback => Ok(Self::Chargeback { id, client }),
         }
     }
 }
@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+
+/// Three letter currency code, e.g. `EUR`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Currency([u8; 3]);
+
+#[derive(Debug, Error)]
+#[error("Invalid currency code {0}")]
+pub struct InvalidCurrency(String);
+
+impl Currency {
+    pub fn as_str(&self) -> &str {
+        // Only ASCII letters are accepted on construction
+        std::str::from_utf8(&self.0).unwrap()
+    }
+}
+
+impl FromStr for Currency {
+    type Err = InvalidCurrency;
+
+    /// Parses a currency code, lowercase codes are accepted
+    ///
+    /// # Example
+    /// ```rust
+    /// use ledger_rs::Currency;
+    ///
+    /// let eur: Currency = "eur".parse().unwrap();
+    ///
+    /// assert_eq!(eur.as_str(), "EUR");
+    /// assert!("EURO".parse::<Currency>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.as_bytes() {
+            &[a, b, c] if s.bytes().all(|b| b.is_ascii_alphabetic()) => Ok(Self([
+                a.to_ascii_uppercase(),
+                b.to_ascii_uppercase(),
+                c.to_ascii_uppercase(),
+            ])),
+            _ => Err(InvalidCurrency(s.to_string())),
+        }
+    }
+}
+
+impl TryFrom<String> for Currency {
+    type Error = InvalidCurrency;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Currency> for String {
+    fn from(currency: Currency) -> Self {
+        currency.as_str().to_string()
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,75 @@
+use crate::{transaction::Moment, Timestamp};
+use serde::Deserialize;
+
+/// Period after a transaction, counted in transactions processed by the account or in
+/// timestamp time
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    /// The period never ends
+    #[default]
+    Unlimited,
+    /// The period ends after the account processed the given number of transactions
+    Transactions(u64),
+    /// The period ends the given time after the timestamp of the transaction, it never ends if
+    /// either transaction has no timestamp
+    Time(Timestamp),
+}
+
+impl Period {
+    /// Checks if the period starting at `from` has ended at `to`
+    pub(crate) fn ended(&self, from: Moment, to: Moment) -> bool {
+        match *self {
+            Self::Unlimited => false,
+            Self::Transactions(count) => to.sequence.saturating_sub(from.sequence) > count,
+            Self::Time(time) => match (from.timestamp, to.timestamp) {
+                (Some(from), Some(to)) => to.saturating_sub(from) > time,
+                _ => false,
+            },
+        }
+    }
+}
+
+/// What happens to a dispute that is still open after its deadline
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpiryAction {
+    Resolve,
+    Chargeback,
+}
+
+/// Deadline of open disputes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DisputeExpiry {
+    /// Period after filing a dispute
+    pub after: Period,
+    /// Action taken when the dispute is still open after the period
+    pub action: ExpiryAction,
+}
+
+/// Rules for filing and closing disputes
+///
+/// # Example
+/// ```rust
+/// use ledger_rs::{DisputeExpiry, DisputePolicy, Period, ExpiryAction};
+///
+/// // Disputes within 60 days, open disputes are resolved after 30 days
+/// let day = 24 * 60 * 60;
+/// let policy = DisputePolicy {
+///     window: Period::Time(60 * day),
+///     expiry: Some(DisputeExpiry {
+///         after: Period::Time(30 * day),
+///         action: ExpiryAction::Resolve,
+///     }),
+/// };
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisputePolicy {
+    /// Period after a deposit or withdrawal in which it can be disputed, later disputes fail
+    /// with [`Error::DisputeWindowExpired`](crate::error::Error::DisputeWindowExpired)
+    pub window: Period,
+    /// Deadline of open disputes, disputes stay open forever without it
+    pub expiry: Option<DisputeExpiry>,
+}
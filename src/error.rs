@@ -1,4 +1,4 @@
-use crate::{ClientId, TxId};
+use crate::{ClientId, Transaction, TxId, ValidatorError};
 use rust_decimal::Decimal;
 use thiserror::Error;
 
@@ -8,20 +8,83 @@ pub enum Error {
     Unauthorized(ClientId, ClientId),
     #[error("Account with ID {0} is locked!")]
     Locked(ClientId),
+    #[error("Account with ID {0} is frozen!")]
+    Frozen(ClientId),
+    #[error("Account with ID {0} is closed!")]
+    Closed(ClientId),
+    #[error("Account with ID {0} has a non-zero balance!")]
+    NonZeroBalance(ClientId),
     #[error("Overflow while trying to add {0} to {1}!")]
     Overflow(Decimal, Decimal),
     #[error("Account with ID {0} has insufficient funds!")]
     InsufficientFunds(ClientId),
+    #[error("Account with ID {0} cannot transfer to itself!")]
+    SelfTransfer(ClientId),
     #[error("Transaction with ID {0} not found for account with ID {1}!")]
     TransactionNotFound(TxId, ClientId),
     #[error("Transaction with ID {0} is not in dispute!")]
     NotInDispute(TxId),
     #[error("Transaction with ID {0} has been or is already disputed!")]
     AlreadyDisputed(TxId),
+    #[error("Dispute amount exceeds the amount of transaction with ID {0}!")]
+    DisputeAmountExceeded(TxId),
     #[error("Amount has to be above zero")]
     AmountTooLow,
+    #[error("Amount {0} has more than {1} decimal places!")]
+    PrecisionExceeded(Decimal, u32),
     #[error("Transaction with ID {0} already exists!")]
     DuplicateTxId(TxId),
+    #[error("Transaction with ID {} conflicts with an earlier transaction with the same ID!", .new.id())]
+    ConflictingTxId {
+        original: Box<Transaction>,
+        new: Box<Transaction>,
+    },
     #[error("Transaction with ID {0} has already been charged back")]
     AlreadyChargedBack(TxId),
+    #[error("Transaction with ID {0} is older than the last activity of account with ID {1}!")]
+    OutOfOrder(TxId, ClientId),
+    #[error("Transaction with ID {0} can no longer be disputed!")]
+    DisputeWindowExpired(TxId),
+    #[error("Transaction with ID {0} is not an open authorization!")]
+    NotAuthorized(TxId),
+    #[error("Capture amount exceeds the open authorization with ID {0}!")]
+    CaptureAmountExceeded(TxId),
+    #[error("Transaction with ID {0} rejected: {1}")]
+    Rejected(TxId, ValidatorError),
+    #[error("Failed to snapshot or restore ledger: {0}")]
+    Snapshot(#[from] serde_json::Error),
+    #[error("Storage error: {0}")]
+    Storage(String),
+}
+
+impl Error {
+    /// Stable machine-readable code of the error, in snake case
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Unauthorized(..) => "unauthorized",
+            Self::Locked(..) => "locked",
+            Self::Frozen(..) => "frozen",
+            Self::Closed(..) => "closed",
+            Self::NonZeroBalance(..) => "non_zero_balance",
+            Self::Overflow(..) => "overflow",
+            Self::InsufficientFunds(..) => "insufficient_funds",
+            Self::SelfTransfer(..) => "self_transfer",
+            Self::TransactionNotFound(..) => "transaction_not_found",
+            Self::NotInDispute(..) => "not_in_dispute",
+            Self::AlreadyDisputed(..) => "already_disputed",
+            Self::DisputeAmountExceeded(..) => "dispute_amount_exceeded",
+            Self::AmountTooLow => "amount_too_low",
+            Self::PrecisionExceeded(..) => "precision_exceeded",
+            Self::DuplicateTxId(..) => "duplicate_tx_id",
+            Self::ConflictingTxId { .. } => "conflicting_tx_id",
+            Self::AlreadyChargedBack(..) => "already_charged_back",
+            Self::OutOfOrder(..) => "out_of_order",
+            Self::DisputeWindowExpired(..) => "dispute_window_expired",
+            Self::NotAuthorized(..) => "not_authorized",
+            Self::CaptureAmountExceeded(..) => "capture_amount_exceeded",
+            Self::Rejected(..) => "rejected",
+            Self::Snapshot(..) => "snapshot",
+            Self::Storage(..) => "storage",
+        }
+    }
 }
@@ -0,0 +1,83 @@
+use crate::{account::Balance, error::Error, ClientId, Currency, Transaction, TxId};
+
+/// Balance of an account in a currency before and after a transaction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BalanceChange {
+    pub client: ClientId,
+    pub currency: Option<Currency>,
+    pub before: Balance,
+    pub after: Balance,
+}
+
+/// State change of a [`Ledger`](crate::Ledger), sent to every [`LedgerObserver`]
+#[derive(Clone, Debug)]
+pub enum Event<'a> {
+    /// A deposit with the given ID was processed, or a transfer to the client
+    Deposited { id: TxId, change: BalanceChange },
+    /// A withdrawal with the given ID was processed, or a transfer from the client
+    Withdrawn { id: TxId, change: BalanceChange },
+    /// Funds were held by the authorization with the given ID
+    Authorized { id: TxId, change: BalanceChange },
+    /// Held funds of the authorization with the given ID were withdrawn
+    Captured { id: TxId, change: BalanceChange },
+    /// The remaining held funds of the authorization with the given ID were released, also
+    /// sent when the authorization expired
+    Voided { id: TxId, change: BalanceChange },
+    /// The transaction with the given ID was disputed, also sent for every dispute of a part
+    /// of the amount, and for both clients of a transfer
+    DisputeOpened { id: TxId, change: BalanceChange },
+    /// The dispute of the transaction with the given ID was resolved
+    DisputeResolved { id: TxId, change: BalanceChange },
+    /// The transaction with the given ID was charged back
+    ChargedBack { id: TxId, change: BalanceChange },
+    /// The account of the client was locked
+    AccountLocked { client: ClientId },
+    /// The transaction failed with the error
+    Rejected {
+        tx: &'a Transaction,
+        error: &'a Error,
+    },
+}
+
+/// Receives the events of a [`Ledger`](crate::Ledger)
+///
+/// Observers are registered on a ledger with
+/// [`Ledger::add_observer`](crate::Ledger::add_observer). Events are sent after the changes
+/// were written to the storage, in the order they happened. Closures with the same signature
+/// as [`LedgerObserver::notify`] are observers too.
+///
+/// # Example
+/// ```rust
+/// use ledger_rs::{Event, Ledger, Transaction};
+/// use rust_decimal::Decimal;
+///
+/// let mut ledger = Ledger::new();
+/// ledger.add_observer(|event: &Event| match event {
+///     Event::DisputeOpened { id, change } if change.after.available() < Decimal::ZERO => {
+///         println!("Dispute of {id} left client {} with a negative balance", change.client);
+///     }
+///     Event::AccountLocked { client } => println!("Account {client} locked"),
+///     _ => {}
+/// });
+/// let tx = Transaction::Deposit {
+///     id: 1.into(),
+///     client: 1.into(),
+///     amount: Decimal::new(2, 0),
+///     currency: None,
+///     timestamp: None,
+/// };
+///
+/// assert!(ledger.process_tx(tx).is_ok());
+/// ```
+pub trait LedgerObserver: Send + Sync {
+    fn notify(&self, event: &Event<'_>);
+}
+
+impl<F> LedgerObserver for F
+where
+    F: Fn(&Event<'_>) + Send + Sync,
+{
+    fn notify(&self, event: &Event<'_>) {
+        self(event)
+    }
+}
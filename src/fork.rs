@@ -0,0 +1,155 @@
+use crate::{
+    error::Error,
+    storage::{Overlay, Storage},
+    AccountRow, Ledger,
+};
+use std::collections::BTreeMap;
+
+/// Change of an output row of an account, made on a fork of a [`Ledger`]
+#[derive(Debug, PartialEq, Eq)]
+pub struct RowChange {
+    /// The row in the original ledger, `None` if it did not exist
+    pub before: Option<AccountRow>,
+    /// The row in the fork, `None` if it no longer exists
+    pub after: Option<AccountRow>,
+}
+
+impl<S: Storage> Ledger<S> {
+    /// Forks the ledger, transactions processed on the fork do not change this ledger
+    ///
+    /// The fork is copy-on-write: it starts out empty and only keeps the accounts and
+    /// transactions that are changed on it. The fork uses the configuration and validators of
+    /// the ledger, but does not notify its observers.
+    ///
+    /// # Example
+    /// ```rust
+    /// use ledger_rs::{Ledger, Transaction};
+    /// use rust_decimal::Decimal;
+    ///
+    /// let ledger = Ledger::new();
+    /// let mut fork = ledger.fork();
+    /// let tx = Transaction::Deposit {
+    ///     id: 1.into(),
+    ///     client: 1.into(),
+    ///     amount: Decimal::new(2, 0),
+    ///     currency: None,
+    ///     timestamp: None,
+    /// };
+    /// assert!(fork.process_tx(tx).is_ok());
+    ///
+    /// let changes = fork.diff().unwrap();
+    ///
+    /// assert_eq!(changes.len(), 1);
+    /// assert!(changes[0].before.is_none());
+    /// assert_eq!(ledger.get_accounts().count(), 0);
+    /// ```
+    pub fn fork(&self) -> Ledger<Overlay<'_, S>> {
+        let mut fork = self.derive(Overlay::new(self.storage()));
+        fork.clear_observers();
+        fork
+    }
+}
+
+impl<'a, S: Storage> Ledger<Overlay<'a, S>> {
+    /// Gets the output rows that changed compared to the original ledger, sorted on client and
+    /// currency
+    ///
+    /// # Errors
+    /// Returns [`Error::Storage`] if the storage backend of the original ledger fails
+    pub fn diff(&self) -> Result<Vec<RowChange>, Error> {
+        let overlay = self.storage();
+        let mut accounts = overlay.changes().accounts.values().collect::<Vec<_>>();
+        accounts.sort_by_key(|account| account.id);
+
+        let mut changes = Vec::new();
+        for account in accounts {
+            let mut rows = BTreeMap::new();
+            if let Some(before) = overlay.base().account(account.id)? {
+                for row in before.rows() {
+                    rows.insert(row.currency, (Some(row), None));
+                }
+            }
+            for row in account.rows() {
+                let currency = row.currency;
+                rows.entry(currency).or_insert((None, None)).1 = Some(row);
+            }
+            changes.extend(
+                rows.into_values()
+                    .filter(|(before, after)| before != after)
+                    .map(|(before, after)| RowChange { before, after }),
+            );
+        }
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Ledger, Transaction};
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn diff() {
+        // Setup
+        let amount = Decimal::new(2, 0);
+        let eur = Some("EUR".parse().unwrap());
+        let mut ledger = Ledger::new();
+        for (id, client) in [(1, 1), (2, 2)] {
+            assert!(ledger
+                .process_tx(Transaction::Deposit {
+                    id: id.into(),
+                    client: client.into(),
+                    amount,
+                    currency: None,
+                    timestamp: None,
+                })
+                .is_ok());
+        }
+        let mut fork = ledger.fork();
+        assert!(fork
+            .process_tx(Transaction::Dispute {
+                id: 1.into(),
+                client: 1.into(),
+                amount: None,
+                timestamp: None,
+            })
+            .is_ok());
+        assert!(fork
+            .process_tx(Transaction::Deposit {
+                id: 3.into(),
+                client: 1.into(),
+                amount,
+                currency: eur,
+                timestamp: None,
+            })
+            .is_ok());
+        // Fails, but touches the account of client 2
+        assert!(fork
+            .process_tx(Transaction::Deposit {
+                id: 1.into(),
+                client: 2.into(),
+                amount,
+                currency: None,
+                timestamp: None,
+            })
+            .is_err());
+
+        // Act
+        let changes = fork.diff().unwrap();
+
+        // Assert
+        assert_eq!(changes.len(), 2);
+        let before = changes[0].before.as_ref().unwrap();
+        let after = changes[0].after.as_ref().unwrap();
+        assert_eq!((before.client, before.currency), (1.into(), None));
+        assert_eq!((before.available, before.held), (amount, Decimal::ZERO));
+        assert_eq!((after.available, after.held), (Decimal::ZERO, amount));
+        assert!(changes[1].before.is_none());
+        assert_eq!(changes[1].after.as_ref().unwrap().currency, eur);
+        let rows = ledger
+            .get_accounts()
+            .flat_map(|account| account.unwrap().rows().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert!(rows.iter().all(|row| row.held == Decimal::ZERO));
+    }
+}
@@ -0,0 +1,239 @@
+use crate::{
+    error::Error, storage::StorageIter, transaction::TransactionKind, ClientId, Transaction,
+    TransactionState, TxId,
+};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{mem, ops::Bound, ops::RangeBounds};
+
+/// A successfully processed transaction in the history of an [`Account`](crate::Account)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub(crate) sequence: u64,
+    pub(crate) transaction: Transaction,
+    pub(crate) state: Option<TransactionState>,
+    /// Account of the entry when it is not the client of the transaction, for the receiving
+    /// side of a transfer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) account: Option<ClientId>,
+    /// Amount that was applied when the amount of the transaction was rounded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) rounded: Option<Decimal>,
+}
+
+impl HistoryEntry {
+    /// Gets the client of the account the entry belongs to
+    pub fn client(&self) -> ClientId {
+        self.account.unwrap_or_else(|| self.transaction.client())
+    }
+
+    /// Gets the position of the entry in the history of the account, starting at 0
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub fn transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    /// Gets the amount that was applied, if the amount of the transaction had more decimal
+    /// places than the precision of the ledger and was rounded
+    pub fn rounded(&self) -> Option<Decimal> {
+        self.rounded
+    }
+
+    /// Gets the state of the deposit, withdrawal, transfer or authorization referenced by the
+    /// transaction, right after it was processed
+    ///
+    /// Administrative transactions do not reference another transaction and have no state.
+    pub fn state(&self) -> Option<TransactionState> {
+        self.state
+    }
+}
+
+/// Iterator over the history of an account in the order the transactions were processed, see
+/// [`Ledger::history`](crate::Ledger::history)
+///
+/// All filters must match for an entry to be yielded, storage errors are always yielded.
+pub struct History<'a> {
+    entries: StorageIter<'a, HistoryEntry>,
+    kinds: Vec<TransactionKind>,
+    ids: (Bound<TxId>, Bound<TxId>),
+    state: Option<TransactionState>,
+}
+
+impl<'a> History<'a> {
+    pub(crate) fn new(entries: StorageIter<'a, HistoryEntry>) -> Self {
+        Self {
+            entries,
+            kinds: Vec::new(),
+            ids: (Bound::Unbounded, Bound::Unbounded),
+            state: None,
+        }
+    }
+
+    /// Only yields transactions of the given kind, can be called again to add more kinds
+    pub fn kind(mut self, kind: TransactionKind) -> Self {
+        self.kinds.push(kind);
+        self
+    }
+
+    /// Only yields transactions with an ID in the given range, in the order of [`Id`](crate::Id)
+    pub fn tx_ids<R: RangeBounds<TxId>>(mut self, ids: R) -> Self {
+        self.ids = (ids.start_bound().cloned(), ids.end_bound().cloned());
+        self
+    }
+
+    /// Only yields transactions that left the referenced deposit or withdrawal in the given
+    /// state, the disputed amount is ignored
+    pub fn state(mut self, state: TransactionState) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    fn matches(&self, entry: &HistoryEntry) -> bool {
+        (self.kinds.is_empty() || self.kinds.contains(&entry.transaction.kind()))
+            && self.ids.contains(&entry.transaction.id())
+            && self.state.is_none_or(|state| {
+                entry
+                    .state
+                    .is_some_and(|s| mem::discriminant(&s) == mem::discriminant(&state))
+            })
+    }
+}
+
+impl<'a> Iterator for History<'a> {
+    type Item = Result<HistoryEntry, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.entries.next()? {
+                Ok(entry) if !self.matches(&entry) => continue,
+                result => return Some(result),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Id, Ledger, Transaction, TransactionKind, TransactionState};
+    use rust_decimal::Decimal;
+
+    fn ledger() -> Ledger {
+        let client = Id::from(1);
+        let amount = Decimal::new(2, 0);
+        let mut ledger = Ledger::new();
+        let txs = [
+            Transaction::Deposit {
+                id: 1.into(),
+                client,
+                amount,
+                currency: None,
+                timestamp: None,
+            },
+            Transaction::Withdrawal {
+                id: 2.into(),
+                client,
+                amount: Decimal::new(5, 0),
+                currency: None,
+                timestamp: None,
+            },
+            Transaction::Deposit {
+                id: 3.into(),
+                client,
+                amount,
+                currency: None,
+                timestamp: None,
+            },
+            Transaction::Dispute {
+                id: 1.into(),
+                client,
+                amount: None,
+                timestamp: None,
+            },
+            Transaction::Dispute {
+                id: 3.into(),
+                client,
+                amount: None,
+                timestamp: None,
+            },
+            Transaction::Resolve {
+                id: 1.into(),
+                client,
+                timestamp: None,
+            },
+            Transaction::Freeze {
+                id: 4.into(),
+                client,
+                timestamp: None,
+            },
+        ];
+        for tx in txs {
+            let _ = ledger.process_tx(tx);
+        }
+        ledger
+    }
+
+    #[test]
+    fn order() {
+        // Setup
+        let ledger = ledger();
+
+        // Act
+        let history = ledger
+            .history(1.into())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        // Assert
+        let sequences = history.iter().map(|e| e.sequence()).collect::<Vec<_>>();
+        let kinds = history
+            .iter()
+            .map(|e| e.transaction().kind())
+            .collect::<Vec<_>>();
+        assert_eq!(sequences, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(
+            kinds,
+            [
+                TransactionKind::Deposit,
+                TransactionKind::Deposit,
+                TransactionKind::Dispute,
+                TransactionKind::Dispute,
+                TransactionKind::Resolve,
+                TransactionKind::Freeze,
+            ]
+        );
+        assert_eq!(history[5].state(), None);
+        assert!(ledger.history(2.into()).next().is_none());
+    }
+
+    #[test]
+    fn filters() {
+        // Setup
+        let ledger = ledger();
+
+        // Act
+        let disputed = ledger
+            .history(1.into())
+            .state(TransactionState::Disputed(Decimal::ZERO))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let first = ledger
+            .history(1.into())
+            .kind(TransactionKind::Deposit)
+            .kind(TransactionKind::Resolve)
+            .tx_ids(..=Id::from(1))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        // Assert
+        assert_eq!(disputed.len(), 2);
+        assert!(disputed
+            .iter()
+            .all(|e| e.transaction().kind() == TransactionKind::Dispute));
+        assert_eq!(first.len(), 2);
+        assert_eq!(first[0].sequence(), 0);
+        assert_eq!(first[1].sequence(), 4);
+    }
+}
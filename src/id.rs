@@ -0,0 +1,306 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{cmp::Ordering, fmt, str::FromStr};
+use thiserror::Error;
+
+/// ID of a client or a transaction, a number, a UUID or a string
+///
+/// An ID keeps the form it was parsed from and is displayed in that form, so errors and reports
+/// show the IDs of the input. A number is at most 64 bits and written without leading zeroes,
+/// a UUID is written in its hyphenated form, anything else is a string of at most
+/// [`Id::MAX_LEN`] bytes. IDs of different forms are never equal, e.g. `123` is not the UUID
+/// `00000000-0000-0000-0000-00000000007b`.
+///
+/// # Example
+/// ```rust
+/// use ledger_rs::Id;
+///
+/// let number: Id = "17".parse().unwrap();
+/// let uuid: Id = "67E55044-10B1-426F-9247-BB680E5FE0C8".parse().unwrap();
+/// let string: Id = "acct-17".parse().unwrap();
+///
+/// assert_eq!(number, Id::from(17));
+/// assert_eq!(uuid.to_string(), "67e55044-10b1-426f-9247-bb680e5fe0c8");
+/// assert_eq!(string.to_string(), "acct-17");
+/// assert_ne!(number, string);
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Id(Repr);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Repr {
+    Number(u64),
+    /// Big-endian bytes, without the alignment of a `u128` an ID is smaller
+    Uuid([u8; 16]),
+    /// The bytes after `len` are always zero
+    Text {
+        len: u8,
+        bytes: [u8; Id::MAX_LEN],
+    },
+}
+
+#[derive(Debug, Error)]
+#[error("Invalid ID {0:?}, expected a number, a UUID or a string of 1 to 40 bytes")]
+pub struct InvalidId(String);
+
+impl Id {
+    /// Maximum length in bytes of a string ID
+    pub const MAX_LEN: usize = 40;
+
+    /// Creates the ID of a UUID from the 128-bit number it represents, e.g. from
+    /// `Uuid::as_u128` of the [uuid](https://crates.io/crates/uuid) crate
+    pub const fn from_uuid(uuid: u128) -> Self {
+        Self(Repr::Uuid(uuid.to_be_bytes()))
+    }
+
+    /// Gets the number of a numeric ID
+    pub fn as_number(&self) -> Option<u64> {
+        match self.0 {
+            Repr::Number(number) => Some(number),
+            _ => None,
+        }
+    }
+
+    /// Gets the 128-bit number of a UUID
+    pub fn as_uuid(&self) -> Option<u128> {
+        match self.0 {
+            Repr::Uuid(uuid) => Some(u128::from_be_bytes(uuid)),
+            _ => None,
+        }
+    }
+
+    /// Gets a string ID, numbers and UUIDs are not strings
+    pub fn as_text(&self) -> Option<&str> {
+        match &self.0 {
+            // Only strings are accepted on construction
+            Repr::Text { len, bytes } => {
+                Some(std::str::from_utf8(&bytes[..*len as usize]).unwrap())
+            }
+            _ => None,
+        }
+    }
+
+    /// Rank of the form of the ID, numbers sort before UUIDs and UUIDs before strings
+    fn rank(&self) -> u8 {
+        match self.0 {
+            Repr::Number(..) => 0,
+            Repr::Uuid(..) => 1,
+            Repr::Text { .. } => 2,
+        }
+    }
+}
+
+impl From<u64> for Id {
+    fn from(number: u64) -> Self {
+        Self(Repr::Number(number))
+    }
+}
+
+/// A number equals a numeric ID
+impl PartialEq<u64> for Id {
+    fn eq(&self, other: &u64) -> bool {
+        self.as_number() == Some(*other)
+    }
+}
+
+impl FromStr for Id {
+    type Err = InvalidId;
+
+    /// Parses an ID, in the first form that fits: a number, a UUID or a string
+    ///
+    /// A number with leading zeroes or a sign is a string, so it is displayed as it was given.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(number) = s.parse::<u64>() {
+            if number.to_string() == s {
+                return Ok(Self(Repr::Number(number)));
+            }
+        }
+        let hyphens = [8, 13, 18, 23];
+        let uuid = s.len() == 36
+            && s.char_indices().all(|(i, c)| match hyphens.contains(&i) {
+                true => c == '-',
+                false => c.is_ascii_hexdigit(),
+            });
+        if uuid {
+            // 32 hexadecimal digits always fit
+            let uuid = u128::from_str_radix(&s.replace('-', ""), 16).unwrap();
+            return Ok(Self::from_uuid(uuid));
+        }
+        match s.len() {
+            len @ 1..=Self::MAX_LEN => {
+                let mut bytes = [0; Self::MAX_LEN];
+                bytes[..len].copy_from_slice(s.as_bytes());
+                Ok(Self(Repr::Text {
+                    len: len as u8,
+                    bytes,
+                }))
+            }
+            _ => Err(InvalidId(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Id {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Repr::Number(number) => write!(f, "{number}"),
+            Repr::Uuid(uuid) => {
+                let hex = format!("{:032x}", u128::from_be_bytes(uuid));
+                write!(
+                    f,
+                    "{}-{}-{}-{}-{}",
+                    &hex[..8],
+                    &hex[8..12],
+                    &hex[12..16],
+                    &hex[16..20],
+                    &hex[20..]
+                )
+            }
+            Repr::Text { .. } => f.write_str(self.as_text().unwrap_or_default()),
+        }
+    }
+}
+
+impl fmt::Debug for Id {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.as_text() {
+            Some(text) => write!(f, "{text:?}"),
+            None => write!(f, "{self}"),
+        }
+    }
+}
+
+impl Ord for Id {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.0, other.0) {
+            (Repr::Number(a), Repr::Number(b)) => a.cmp(&b),
+            (Repr::Uuid(a), Repr::Uuid(b)) => a.cmp(&b),
+            (Repr::Text { .. }, Repr::Text { .. }) => self.as_text().cmp(&other.as_text()),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for Id {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A number is serialized as a number, a UUID or a string as a string
+impl Serialize for Id {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Repr::Number(number) => serializer.serialize_u64(number),
+            _ => serializer.collect_str(self),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Id {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(IdVisitor)
+    }
+}
+
+struct IdVisitor;
+
+impl<'de> de::Visitor<'de> for IdVisitor {
+    type Value = Id;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a number, a UUID or a string of 1 to 40 bytes")
+    }
+
+    fn visit_u64<E: de::Error>(self, number: u64) -> Result<Id, E> {
+        Ok(Id::from(number))
+    }
+
+    fn visit_i64<E: de::Error>(self, number: i64) -> Result<Id, E> {
+        u64::try_from(number)
+            .map(Id::from)
+            .map_err(|_| E::invalid_value(de::Unexpected::Signed(number), &self))
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Id, E> {
+        s.parse().map_err(E::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Id;
+
+    #[test]
+    fn parse() {
+        // Setup
+        let inputs = [
+            "123",
+            "00000000-0000-0000-0000-00000000007b",
+            "00000000-0000-0000-0000-000000000000",
+            "0123",
+            "18446744073709551616",
+            "acct-17",
+        ];
+
+        // Act
+        let ids = inputs.map(|input| input.parse::<Id>().unwrap());
+
+        // Assert
+        assert_eq!(ids[0], Id::from(123));
+        assert_eq!(ids[1], Id::from_uuid(123));
+        assert_eq!(ids[2], Id::from_uuid(0));
+        assert_eq!(ids[3].as_text(), Some("0123"));
+        assert_eq!(ids[4].as_text(), Some("18446744073709551616"));
+        assert_eq!(ids[5].as_text(), Some("acct-17"));
+        for (id, input) in ids.iter().zip(inputs) {
+            assert_eq!(id.to_string(), input);
+        }
+        assert_ne!(ids[0], ids[1]);
+    }
+
+    #[test]
+    fn parse_invalid() {
+        // Act
+        let empty = "".parse::<Id>();
+        let long = "x".repeat(Id::MAX_LEN + 1).parse::<Id>();
+
+        // Assert
+        assert!(empty.is_err());
+        assert!(long.is_err());
+    }
+
+    #[test]
+    fn serde() {
+        // Setup
+        let ids = ["7", "67e55044-10b1-426f-9247-bb680e5fe0c8", "acct-17"]
+            .map(|input| input.parse::<Id>().unwrap());
+
+        // Act
+        let json = serde_json::to_string(&ids).unwrap();
+        let result = serde_json::from_str::<[Id; 3]>(&json).unwrap();
+
+        // Assert
+        assert_eq!(
+            json,
+            r#"[7,"67e55044-10b1-426f-9247-bb680e5fe0c8","acct-17"]"#
+        );
+        assert_eq!(result, ids);
+    }
+
+    #[test]
+    fn order() {
+        // Setup
+        let mut ids = ["b", "10", "a", "00000000-0000-0000-0000-000000000001", "9"]
+            .map(|input| input.parse::<Id>().unwrap());
+
+        // Act
+        ids.sort();
+
+        // Assert
+        let sorted = ids.map(|id| id.to_string());
+        assert_eq!(
+            sorted,
+            ["9", "10", "00000000-0000-0000-0000-000000000001", "a", "b"]
+        );
+    }
+}
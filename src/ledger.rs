@@ -1,10 +1,33 @@
-use crate::{account::Account, error::Error, ClientId, Transaction, TxId};
-use std::collections::{HashMap, HashSet};
+use crate::{
+    account::{Account, AccountStatus},
+    error::Error,
+    snapshot,
+    storage::{MemoryStorage, Overlay, Storage},
+    ClientId, Currency, Event, ExpiryAction, History, LedgerConfig, LedgerObserver, Period,
+    Timestamp, TimestampPolicy, Transaction, TransactionState, TransactionValidator, TxId,
+    TxRecord,
+};
+use rust_decimal::Decimal;
+use std::{
+    io::{Read, Write},
+    sync::Arc,
+};
 use tracing::debug;
 
-pub struct Ledger {
-    accounts: HashMap<ClientId, Account>,
-    transactions: HashSet<TxId>,
+/// Outcome of a transaction that was accepted by [`Ledger::process_tx`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The transaction was processed
+    Processed,
+    /// The transaction is an exact replay of a processed deposit or withdrawal and was ignored
+    Duplicate,
+}
+
+pub struct Ledger<S: Storage = MemoryStorage> {
+    storage: S,
+    config: LedgerConfig,
+    validators: Vec<Arc<dyn TransactionValidator>>,
+    observers: Vec<Arc<dyn LedgerObserver>>,
 }
 
 impl Default for Ledger {
@@ -15,44 +38,666 @@ impl Default for Ledger {
 
 impl Ledger {
     pub fn new() -> Self {
+        Self::with_storage(MemoryStorage::default())
+    }
+
+    /// Creates an in-memory ledger with the given configuration
+    pub fn with_config(config: LedgerConfig) -> Self {
+        let mut ledger = Self::new();
+        ledger.set_config(config);
+        ledger
+    }
+
+    /// Restores an in-memory ledger from a snapshot written by [`Ledger::snapshot`]
+    ///
+    /// # Errors
+    /// Returns [`Error::Snapshot`] if reading or deserializing fails
+    pub fn restore<R: Read>(reader: R) -> Result<Self, Error> {
+        Self::restore_with_storage(MemoryStorage::default(), reader)
+    }
+}
+
+impl<S: Storage> Ledger<S> {
+    /// Creates a ledger on top of the given storage backend
+    ///
+    /// Accounts and transactions already in the storage are kept.
+    pub fn with_storage(storage: S) -> Self {
         Self {
-            accounts: HashMap::new(),
-            transactions: HashSet::new(),
+            storage,
+            config: LedgerConfig::default(),
+            validators: Vec::new(),
+            observers: Vec::new(),
+        }
+    }
+
+    /// Restores a snapshot written by [`Ledger::snapshot`] into the given storage backend
+    ///
+    /// # Errors
+    /// Returns [`Error::Snapshot`] if reading or deserializing fails, or [`Error::Storage`] if
+    /// the storage backend fails
+    pub fn restore_with_storage<R: Read>(mut storage: S, reader: R) -> Result<Self, Error> {
+        snapshot::read(&mut storage, reader)?;
+        Ok(Self::with_storage(storage))
+    }
+
+    /// Gets the storage backend
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    /// Consumes the ledger, returning the storage backend
+    pub fn into_storage(self) -> S {
+        self.storage
+    }
+
+    pub fn config(&self) -> &LedgerConfig {
+        &self.config
+    }
+
+    /// Replaces the configuration, the new rules apply to the transactions processed after it
+    pub fn set_config(&mut self, config: LedgerConfig) {
+        self.config = config;
+    }
+
+    /// Registers a validator, validators run in the order they were registered
+    pub fn add_validator<V: TransactionValidator + 'static>(&mut self, validator: V) {
+        self.validators.push(Arc::new(validator));
+    }
+
+    /// Registers an observer, observers are notified in the order they were registered
+    pub fn add_observer<O: LedgerObserver + 'static>(&mut self, observer: O) {
+        self.observers.push(Arc::new(observer));
+    }
+
+    /// Creates a ledger with the same configuration, validators and observers on top of
+    /// another storage
+    pub(crate) fn derive<T: Storage>(&self, storage: T) -> Ledger<T> {
+        Ledger {
+            storage,
+            config: self.config.clone(),
+            validators: self.validators.clone(),
+            observers: self.observers.clone(),
+        }
+    }
+
+    /// Removes all observers
+    pub(crate) fn clear_observers(&mut self) {
+        self.observers.clear();
+    }
+
+    pub(crate) fn notify(&self, event: &Event<'_>) {
+        for observer in &self.observers {
+            observer.notify(event);
         }
     }
 
     /// Public interface for processing transactions
     ///
+    /// The observers of the ledger are notified of the events of the transaction, including
+    /// closed expired disputes, and of [`Event::Rejected`] if it fails.
+    ///
+    /// A deposit or withdrawal with a used ID that is equal to the processed deposit or
+    /// withdrawal with the ID, e.g. a retry, is ignored and gives [`Outcome::Duplicate`]. A
+    /// different transaction with the ID fails with [`Error::ConflictingTxId`].
+    ///
     /// # Example
     /// ```rust
     /// use ledger_rs::{Ledger, Transaction};
     ///
     /// let mut ledger = Ledger::new();
-    /// let tx = Transaction::Dispute { id: 1, client: 1 };
+    /// let tx = Transaction::Dispute {
+    ///     id: 1.into(),
+    ///     client: 1.into(),
+    ///     amount: None,
+    ///     timestamp: None,
+    /// };
     ///
     /// assert!(ledger.process_tx(tx).is_err());
     /// ```
     /// # Errors
     /// Could return an error, see [`Error`] for more
-    pub fn process_tx(&mut self, tx: Transaction) -> Result<(), Error> {
+    pub fn process_tx(&mut self, tx: Transaction) -> Result<Outcome, Error> {
+        let rejected = (!self.observers.is_empty()).then(|| tx.clone());
+        let mut events = Vec::new();
+        let result = self.process(tx, &mut events);
+        for event in &events {
+            self.notify(event);
+        }
+        if let (Some(tx), Err(error)) = (&rejected, &result) {
+            self.notify(&Event::Rejected { tx, error });
+        }
+        result
+    }
+
+    /// Processes a transaction, adding the events to `events` once the account is stored
+    fn process(
+        &mut self,
+        tx: Transaction,
+        events: &mut Vec<Event<'static>>,
+    ) -> Result<Outcome, Error> {
         let id = tx.id();
-        let client = tx.client();
-        let account = self.accounts.entry(client).or_insert_with(|| {
-            debug!("Account with ID {client} not found, creating new account");
-            Account::new(client)
-        });
+        let (mut account, exists) = self.load_account(tx.client())?;
+
+        let reserve = self.config.reserve_failed_tx_ids();
+        let result = match tx {
+            _ if tx.reserves_id() && self.config.unique_tx_ids() => {
+                let used = match reserve {
+                    true => !self.storage.insert_tx_id(id)?,
+                    false => self.storage.contains_tx_id(id)?,
+                };
+                match used {
+                    true => self.replay(tx),
+                    false => {
+                        let result = self.apply(&mut account, tx);
+                        if result.is_ok() && !reserve {
+                            self.storage.insert_tx_id(id)?;
+                        }
+                        result
+                    }
+                }
+            }
+            _ => self.apply(&mut account, tx),
+        };
+        if exists || result.is_ok() || self.config.create_failed_accounts() {
+            let account_events = account.take_events();
+            self.storage.put_account(account)?;
+            events.extend(account_events);
+        }
+        result
+    }
+
+    /// Processes a batch of transactions, either all of them are applied or none of them
+    ///
+    /// The batch is processed on an [`Overlay`] of the storage. Only when every transaction
+    /// succeeds, the changes are written to the storage.
+    ///
+    /// The observers of the ledger are notified of the events of the batch after the changes
+    /// are written, or only of the rejection of the failing transaction.
+    ///
+    /// # Example
+    /// ```rust
+    /// use ledger_rs::{error::Error, Ledger, Transaction};
+    /// use rust_decimal::Decimal;
+    ///
+    /// let mut ledger = Ledger::new();
+    /// let amount = Decimal::new(2, 0);
+    /// let batch = vec![
+    ///     Transaction::Deposit { id: 1.into(), client: 1.into(), amount, currency: None, timestamp: None },
+    ///     Transaction::Withdrawal { id: 2.into(), client: 1.into(), amount: amount * amount, currency: None, timestamp: None },
+    /// ];
+    ///
+    /// let result = ledger.apply_batch(batch);
+    ///
+    /// assert!(matches!(result, Err((1, Error::InsufficientFunds(..)))));
+    /// assert_eq!(ledger.get_accounts().count(), 0);
+    /// ```
+    /// # Errors
+    /// Returns the index in the batch and the error of the first failing transaction. If
+    /// writing the changes to the storage fails, the index is the length of the batch.
+    pub fn apply_batch<I>(&mut self, txs: I) -> Result<(), (usize, Error)>
+    where
+        I: IntoIterator<Item = Transaction>,
+    {
+        let mut batch = self.derive(Overlay::new(&self.storage));
+        batch.clear_observers();
+        let mut events = Vec::new();
+        let mut len = 0;
+        for (index, tx) in txs.into_iter().enumerate() {
+            let rejected = (!self.observers.is_empty()).then(|| tx.clone());
+            if let Err(error) = batch.process(tx, &mut events) {
+                if let Some(tx) = &rejected {
+                    self.notify(&Event::Rejected { tx, error: &error });
+                }
+                return Err((index, error));
+            }
+            len += 1;
+        }
+        let changes = batch.into_storage().into_changes();
+        self.storage.commit(changes).map_err(|e| (len, e))?;
+        for event in &events {
+            self.notify(event);
+        }
+        Ok(())
+    }
+
+    /// Closes the open disputes past their deadline of all accounts, as if a transaction with
+    /// the given timestamp was processed for each account
+    ///
+    /// Does nothing without [`DisputePolicy::expiry`](crate::DisputePolicy::expiry).
+    ///
+    /// # Errors
+    /// Returns [`Error::Storage`] if the storage backend fails
+    pub fn expire_disputes(&mut self, now: Timestamp) -> Result<(), Error> {
+        if self.config.disputes().expiry.is_none() {
+            return Ok(());
+        }
+        self.expire_accounts(
+            now,
+            |account| account.open_disputes().next().is_some(),
+            Self::expire_open_disputes,
+        )
+    }
+
+    /// Voids the open authorizations past their expiry of all accounts, as if a transaction
+    /// with the given timestamp was processed for each account
+    ///
+    /// Does nothing without
+    /// [`LedgerConfig::authorization_expiry`](crate::LedgerConfig::authorization_expiry).
+    ///
+    /// # Errors
+    /// Returns [`Error::Storage`] if the storage backend fails
+    pub fn expire_authorizations(&mut self, now: Timestamp) -> Result<(), Error> {
+        if self.config.authorization_expiry() == Period::Unlimited {
+            return Ok(());
+        }
+        self.expire_accounts(
+            now,
+            |account| account.open_authorizations().next().is_some(),
+            Self::expire_open_authorizations,
+        )
+    }
+
+    /// Runs `expire` on the accounts for which `open` is true, and stores them
+    fn expire_accounts(
+        &mut self,
+        now: Timestamp,
+        open: fn(&Account) -> bool,
+        expire: fn(&mut Self, &mut Account, Option<Timestamp>) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let accounts = self
+            .storage
+            .accounts()
+            .filter(|account| match account {
+                Ok(account) => open(account),
+                Err(..) => true,
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for mut account in accounts {
+            expire(self, &mut account, Some(now))?;
+            let events = account.take_events();
+            self.storage.put_account(account)?;
+            for event in &events {
+                self.notify(event);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the credit limit of a client in a currency, creating the account if it does not exist
+    ///
+    /// Unlike [`Transaction::CreditLimit`], this is not kept in the account history.
+    ///
+    /// # Errors
+    /// Returns [`Error::AmountTooLow`] for a negative limit, [`Error::PrecisionExceeded`] for a
+    /// limit that cannot be rounded, [`Error::Closed`] for a closed account, or
+    /// [`Error::Storage`] if the storage backend fails
+    pub fn set_credit_limit(
+        &mut self,
+        client: ClientId,
+        currency: Option<Currency>,
+        limit: Decimal,
+    ) -> Result<(), Error> {
+        let (mut account, _) = self.load_account(client)?;
+        if account.status() == AccountStatus::Closed {
+            return Err(Error::Closed(client));
+        }
+        account.set_credit_limit(limit, currency, &self.config)?;
+        self.storage.put_account(account)
+    }
+
+    /// Creates an account without processing a transaction, if it does not exist yet
+    ///
+    /// Does nothing if failed transactions do not create accounts.
+    pub(crate) fn open_account(&mut self, client: ClientId) -> Result<(), Error> {
+        let (account, exists) = self.load_account(client)?;
+        if exists || !self.config.create_failed_accounts() {
+            return Ok(());
+        }
+        self.storage.put_account(account)
+    }
+
+    /// Checks and processes a transaction on the account
+    fn apply(&mut self, account: &mut Account, tx: Transaction) -> Result<Outcome, Error> {
+        self.check(account, &tx)?;
+        match self.expiring(account) {
+            true => self.expire_and_execute(account, tx)?,
+            false => self.execute(account, tx)?,
+        }
+        Ok(Outcome::Processed)
+    }
+
+    /// Whether the account has open disputes or authorizations that can expire
+    fn expiring(&self, account: &Account) -> bool {
+        (self.config.disputes().expiry.is_some() && account.open_disputes().next().is_some())
+            || (self.config.authorization_expiry() != Period::Unlimited
+                && account.open_authorizations().next().is_some())
+    }
+
+    /// Closes the expired disputes and authorizations of the account before it processes the
+    /// transaction
+    ///
+    /// Only a transaction that is accepted may drive the expiry with its timestamp. The expiry
+    /// is kept when the transaction succeeds, or when it would have succeeded without the
+    /// expiry, e.g. a deposit to an account that is locked by an expired dispute. Otherwise the
+    /// expiry is discarded along with the transaction.
+    fn expire_and_execute(&mut self, account: &mut Account, tx: Transaction) -> Result<(), Error> {
+        let mut expired = account.clone();
+        let mut staged = self.derive(Overlay::new(&self.storage));
+        staged.expire(&mut expired, tx.timestamp())?;
+        let error = match staged.execute(&mut expired, tx.clone()) {
+            Ok(()) => None,
+            Err(e @ Error::Storage(..)) => return Err(e),
+            Err(e) => {
+                let mut probe = self.derive(Overlay::new(&self.storage));
+                probe.execute(&mut account.clone(), tx)?;
+                Some(e)
+            }
+        };
+        let changes = staged.into_storage().into_changes();
+        self.storage.commit(changes)?;
+        *account = expired;
+        error.map_or(Ok(()), Err)
+    }
+
+    /// Processes a transaction on the account, and on the account of the receiving client if
+    /// it concerns a transfer
+    fn execute(&mut self, account: &mut Account, tx: Transaction) -> Result<(), Error> {
+        let (to, record) = match self.counterpart(account, &tx)? {
+            Some(counterpart) => counterpart,
+            None => return account.process_tx(tx, &mut self.storage, &self.config),
+        };
+        // Both accounts are changed on an overlay, which is only written when both succeed
+        let mut overlay = Overlay::new(&self.storage);
+        let mut owner = account.clone();
+        let (mut other, _) = self.load_account(to)?;
+        owner.process_tx(tx.clone(), &mut overlay, &self.config)?;
+        other.process_counterpart(tx, record, &mut overlay, &self.config)?;
+        let events = other.take_events();
+        overlay.put_account(other)?;
+        let changes = overlay.into_changes();
+        self.storage.commit(changes)?;
+        owner.add_events(events);
+        *account = owner;
+        Ok(())
+    }
+
+    /// Gets the receiving client of the transfer a transaction of the account concerns, along
+    /// with the transfer if it was processed before
+    fn counterpart(
+        &self,
+        account: &Account,
+        tx: &Transaction,
+    ) -> Result<Option<(ClientId, Option<TxRecord>)>, Error> {
+        match *tx {
+            Transaction::Transfer { to, .. } if to != account.id => Ok(Some((to, None))),
+            Transaction::Dispute { id, .. }
+            | Transaction::Resolve { id, .. }
+            | Transaction::Chargeback { id, .. } => Ok(self
+                .storage
+                .transaction(id)?
+                .filter(|record| record.transaction.client() == account.id)
+                .and_then(|record| Some((record.transaction.counterpart()?, Some(record))))),
+            _ => Ok(None),
+        }
+    }
+
+    /// Compares a deposit or withdrawal with a used ID to the processed transaction with the ID
+    ///
+    /// Fails with [`Error::DuplicateTxId`] if the ID was used by a transaction that failed.
+    pub(crate) fn replay(&self, tx: Transaction) -> Result<Outcome, Error> {
+        match self.storage.transaction(tx.id())? {
+            Some(record) if record.transaction == tx => {
+                debug!("Ignoring replay of transaction with ID {}", tx.id());
+                Ok(Outcome::Duplicate)
+            }
+            Some(record) => Err(Error::ConflictingTxId {
+                original: Box::new(record.transaction),
+                new: Box::new(tx),
+            }),
+            None => Err(Error::DuplicateTxId(tx.id())),
+        }
+    }
+
+    /// Checks the policies and validators of the ledger before the account processes the
+    /// transaction
+    fn check(&self, account: &Account, tx: &Transaction) -> Result<(), Error> {
+        if let (TimestampPolicy::RejectOutOfOrder, Some(timestamp), Some(last)) = (
+            self.config.timestamps(),
+            tx.timestamp(),
+            account.last_activity(),
+        ) {
+            if timestamp < last {
+                return Err(Error::OutOfOrder(tx.id(), account.id));
+            }
+        }
+        if tx.reserves_id() && !self.config.unique_tx_ids() {
+            // Records are kept per ID, a reused ID replaces the record of the earlier transaction
+            if let Some(record) = self.storage.transaction(tx.id())? {
+                let open = matches!(
+                    record.state,
+                    TransactionState::Disputed(..) | TransactionState::Authorized(..)
+                );
+                if open || record.transaction.client() != tx.client() {
+                    return Err(Error::ConflictingTxId {
+                        original: Box::new(record.transaction),
+                        new: Box::new(tx.clone()),
+                    });
+                }
+            }
+        }
+        if let Transaction::Dispute { id, .. } = *tx {
+            // A missing transaction is reported by the account
+            if let Some(record) = self.storage.transaction(id)? {
+                let now = account.moment(tx.timestamp());
+                if record.transaction.client() == account.id
+                    && self.config.disputes().window.ended(record.processed, now)
+                {
+                    return Err(Error::DisputeWindowExpired(id));
+                }
+            }
+        }
+        for validator in &self.validators {
+            validator
+                .validate(tx, account)
+                .map_err(|e| Error::Rejected(tx.id(), e))?;
+        }
+        Ok(())
+    }
+
+    /// Closes the open disputes and voids the open authorizations of the account that are past
+    /// their deadline
+    fn expire(&mut self, account: &mut Account, timestamp: Option<Timestamp>) -> Result<(), Error> {
+        self.expire_open_disputes(account, timestamp)?;
+        self.expire_open_authorizations(account, timestamp)
+    }
+
+    /// Closes the open disputes of the account that are past their deadline
+    fn expire_open_disputes(
+        &mut self,
+        account: &mut Account,
+        timestamp: Option<Timestamp>,
+    ) -> Result<(), Error> {
+        let (deadline, action) = match self.config.disputes().expiry {
+            Some(expiry) => (expiry.after, expiry.action),
+            None => return Ok(()),
+        };
+        let now = account.moment(timestamp);
+        let disputes = account.open_disputes().collect::<Vec<_>>();
+        for id in disputes {
+            let disputed = self.storage.transaction(id)?.and_then(|r| r.disputed);
+            if !disputed.is_some_and(|disputed| deadline.ended(disputed, now)) {
+                continue;
+            }
+            let client = account.id;
+            let tx = match action {
+                ExpiryAction::Resolve => Transaction::Resolve {
+                    id,
+                    client,
+                    timestamp,
+                },
+                ExpiryAction::Chargeback => Transaction::Chargeback {
+                    id,
+                    client,
+                    timestamp,
+                },
+            };
+            debug!("Dispute of transaction with ID {id} expired, attempting {action:?}");
+            match self.execute(account, tx) {
+                Err(e @ Error::Storage(..)) => return Err(e),
+                Err(e) => debug!("Failed to close expired dispute: {e}"),
+                Ok(()) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Voids the open authorizations of the account that are past their expiry
+    fn expire_open_authorizations(
+        &mut self,
+        account: &mut Account,
+        timestamp: Option<Timestamp>,
+    ) -> Result<(), Error> {
+        let expiry = self.config.authorization_expiry();
+        if expiry == Period::Unlimited {
+            return Ok(());
+        }
+        let now = account.moment(timestamp);
+        let authorizations = account.open_authorizations().collect::<Vec<_>>();
+        for id in authorizations {
+            let processed = self.storage.transaction(id)?.map(|r| r.processed);
+            if !processed.is_some_and(|processed| expiry.ended(processed, now)) {
+                continue;
+            }
+            let tx = Transaction::Void {
+                id,
+                client: account.id,
+                timestamp,
+            };
+            debug!("Authorization with ID {id} expired, attempting void");
+            match self.execute(account, tx) {
+                Err(e @ Error::Storage(..)) => return Err(e),
+                Err(e) => debug!("Failed to void expired authorization: {e}"),
+                Ok(()) => {}
+            }
+        }
+        Ok(())
+    }
 
-        if let Transaction::Deposit { .. } | Transaction::Withdrawal { .. } = tx {
-            if self.transactions.contains(&id) {
-                return Err(Error::DuplicateTxId(id));
+    /// Gets an account from storage or creates a new one, along with whether it exists
+    fn load_account(&self, client: ClientId) -> Result<(Account, bool), Error> {
+        match self.storage.account(client)? {
+            Some(account) => Ok((account, true)),
+            None => {
+                debug!("Account with ID {client} not found, creating new account");
+                Ok((Account::new(client), false))
             }
-            self.transactions.insert(id);
-            account.process_tx(tx)
-        } else {
-            account.process_tx(tx)
         }
     }
 
+    /// Gets the account with the given ID, if it exists
+    ///
+    /// # Errors
+    /// Returns [`Error::Storage`] if the storage backend fails
+    pub fn account(&self, client: ClientId) -> Result<Option<Account>, Error> {
+        self.storage.account(client)
+    }
+
+    /// Gets a processed deposit, withdrawal, transfer or authorization and the ID of the client
+    /// that owns it
+    ///
+    /// # Example
+    /// ```rust
+    /// use ledger_rs::{Ledger, Transaction, TransactionState};
+    /// use rust_decimal::Decimal;
+    ///
+    /// let mut ledger = Ledger::new();
+    /// let tx = Transaction::Deposit {
+    ///     id: 1234.into(),
+    ///     client: 7.into(),
+    ///     amount: Decimal::new(2, 0),
+    ///     currency: None,
+    ///     timestamp: None,
+    /// };
+    /// assert!(ledger.process_tx(tx).is_ok());
+    /// let tx = Transaction::Dispute {
+    ///     id: 1234.into(),
+    ///     client: 7.into(),
+    ///     amount: None,
+    ///     timestamp: None,
+    /// };
+    /// assert!(ledger.process_tx(tx).is_ok());
+    ///
+    /// let (client, tx) = ledger.transaction(1234.into()).unwrap().unwrap();
+    /// let state = ledger.dispute_state(1234.into()).unwrap();
+    ///
+    /// assert_eq!(client, 7);
+    /// assert_eq!(tx.amount(), Some(Decimal::new(2, 0)));
+    /// assert_eq!(state, Some(TransactionState::Disputed(Decimal::new(2, 0))));
+    /// ```
+    /// # Errors
+    /// Returns [`Error::Storage`] if the storage backend fails
+    pub fn transaction(&self, id: TxId) -> Result<Option<(ClientId, Transaction)>, Error> {
+        Ok(self
+            .storage
+            .transaction(id)?
+            .map(|record| (record.transaction.client(), record.transaction)))
+    }
+
+    /// Gets the state of a processed deposit, withdrawal, transfer or authorization
+    ///
+    /// A deposit, withdrawal or transfer is [`TransactionState::Normal`],
+    /// [`TransactionState::Disputed`] or [`TransactionState::Chargedback`]. An authorization is
+    /// [`TransactionState::Authorized`], [`TransactionState::Captured`] or
+    /// [`TransactionState::Voided`].
+    ///
+    /// # Errors
+    /// Returns [`Error::Storage`] if the storage backend fails
+    pub fn dispute_state(&self, id: TxId) -> Result<Option<TransactionState>, Error> {
+        Ok(self.storage.transaction(id)?.map(|record| record.state))
+    }
+
+    /// Iterates over the successfully processed transactions of a client, in the order they were
+    /// processed
+    ///
+    /// # Example
+    /// ```rust
+    /// use ledger_rs::{Id, Ledger, Transaction, TransactionKind};
+    /// use rust_decimal::Decimal;
+    ///
+    /// let mut ledger = Ledger::new();
+    /// for id in 1..=3u64 {
+    ///     let tx = Transaction::Deposit {
+    ///         id: id.into(),
+    ///         client: 7.into(),
+    ///         amount: Decimal::new(2, 0),
+    ///         currency: None,
+    ///         timestamp: None,
+    ///     };
+    ///     assert!(ledger.process_tx(tx).is_ok());
+    /// }
+    /// let tx = Transaction::Dispute {
+    ///     id: 2.into(),
+    ///     client: 7.into(),
+    ///     amount: None,
+    ///     timestamp: None,
+    /// };
+    /// assert!(ledger.process_tx(tx).is_ok());
+    ///
+    /// let disputes = ledger
+    ///     .history(7.into())
+    ///     .kind(TransactionKind::Dispute)
+    ///     .tx_ids(Id::from(2)..)
+    ///     .collect::<Result<Vec<_>, _>>()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(ledger.history(7.into()).count(), 4);
+    /// assert_eq!(disputes.len(), 1);
+    /// assert_eq!(disputes[0].sequence(), 3);
+    /// ```
+    pub fn history(&self, client: ClientId) -> History<'_> {
+        History::new(self.storage.history(client))
+    }
+
     /// Gets all accounts
     ///
     /// # Example
@@ -65,41 +710,1240 @@ impl Ledger {
     ///
     /// assert!(ledger
     ///     .process_tx(Transaction::Deposit {
-    ///         id: 1,
-    ///         client: 1,
+    ///         id: 1.into(),
+    ///         client: 1.into(),
     ///         amount: Decimal::new(2, 0),
+    ///         currency: None,
+    ///         timestamp: None,
     ///     })
     ///     .is_ok());
     /// assert_eq!(ledger.get_accounts().count(), 1);
     /// ```
-    pub fn get_accounts(&self) -> impl Iterator<Item = &Account> {
-        self.accounts.values()
+    pub fn get_accounts(&self) -> impl Iterator<Item = Result<Account, Error>> + '_ {
+        self.storage.accounts()
+    }
+
+    /// Writes the full state of the ledger as JSON, including the transaction history needed
+    /// for later disputes
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use ledger_rs::{Ledger, Transaction};
+    /// use rust_decimal::Decimal;
+    ///
+    /// let mut ledger = Ledger::new();
+    /// let tx = Transaction::Deposit {
+    ///     id: 1.into(),
+    ///     client: 1.into(),
+    ///     amount: Decimal::new(2, 0),
+    ///     currency: None,
+    ///     timestamp: None,
+    /// };
+    /// assert!(ledger.process_tx(tx).is_ok());
+    ///
+    /// let mut snapshot = Vec::new();
+    /// assert!(ledger.snapshot(&mut snapshot).is_ok());
+    /// let mut restored = Ledger::restore(snapshot.as_slice()).unwrap();
+    ///
+    /// let tx = Transaction::Dispute {
+    ///     id: 1.into(),
+    ///     client: 1.into(),
+    ///     amount: None,
+    ///     timestamp: None,
+    /// };
+    /// assert!(restored.process_tx(tx).is_ok());
+    /// ```
+    /// # Errors
+    /// Returns [`Error::Snapshot`] if serializing or writing fails, or [`Error::Storage`] if
+    /// the storage backend fails
+    pub fn snapshot<W: Write>(&self, writer: W) -> Result<(), Error> {
+        snapshot::write(&self.storage, writer)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use rust_decimal::Decimal;
+    use std::sync::{Arc, Mutex};
 
-    use crate::{error::Error, Ledger, Transaction};
+    use crate::{
+        error::Error,
+        storage::{MemoryStorage, Storage, StorageIter},
+        transaction::TransactionState,
+        Account, ClientId, DisputeExpiry, DisputePolicy, Event, ExpiryAction, HistoryEntry, Id,
+        Ledger, LedgerConfig, Outcome, Period, Rounding, Timestamp, TimestampPolicy, Transaction,
+        TransactionKind, TxId, TxRecord,
+    };
 
     #[test]
     fn duplicate_tx_id() {
         // Setup
-        let id = 1;
-        let client = 1;
+        let id = Id::from(1);
+        let client = Id::from(1);
+        let amount = Decimal::new(2, 0);
+        let tx = Transaction::Deposit {
+            id,
+            client,
+            amount,
+            currency: None,
+            timestamp: None,
+        };
+        let mut ledger = Ledger::default();
+
+        assert!(ledger
+            .process_tx(Transaction::Deposit {
+                id,
+                client,
+                amount,
+                currency: None,
+                timestamp: None,
+            })
+            .is_ok());
+
+        // Act
+        let result = ledger.process_tx(tx);
+
+        // Assert
+        assert!(matches!(result, Ok(Outcome::Duplicate)));
+        let account = ledger.account(client).unwrap().unwrap();
+        assert_eq!(account.balance(None).total(), amount);
+        assert_eq!(ledger.history(client).count(), 1);
+    }
+
+    #[test]
+    fn conflicting_tx_id() {
+        // Setup
+        let client = Id::from(1);
         let amount = Decimal::new(2, 0);
-        let tx = Transaction::Deposit { id, client, amount };
         let mut ledger = Ledger::default();
+        let deposit = Transaction::Deposit {
+            id: 1.into(),
+            client,
+            amount,
+            currency: None,
+            timestamp: None,
+        };
+        let withdrawal = Transaction::Withdrawal {
+            id: 2.into(),
+            client,
+            amount: amount * amount,
+            currency: None,
+            timestamp: None,
+        };
+        assert!(ledger.process_tx(deposit.clone()).is_ok());
+        assert!(ledger.process_tx(withdrawal.clone()).is_err());
+
+        // Act
+        let conflict = ledger.process_tx(Transaction::Deposit {
+            id: 1.into(),
+            client,
+            amount: amount * amount,
+            currency: None,
+            timestamp: None,
+        });
+        let failed = ledger.process_tx(withdrawal);
+
+        // Assert
+        match conflict {
+            Err(Error::ConflictingTxId { original, new }) => {
+                assert_eq!(*original, deposit);
+                assert_eq!(new.amount(), Some(amount * amount));
+            }
+            _ => panic!("unexpected result {conflict:?}"),
+        }
+        assert!(matches!(failed, Err(Error::DuplicateTxId(id)) if id == 2));
+        let account = ledger.account(client).unwrap().unwrap();
+        assert_eq!(account.balance(None).total(), amount);
+    }
+
+    #[test]
+    fn transaction_of_other_client() {
+        // Setup
+        let id = Id::from(1);
+        let amount = Decimal::new(2, 0);
+        let mut ledger = Ledger::default();
+
+        assert!(ledger
+            .process_tx(Transaction::Deposit {
+                id,
+                client: 1.into(),
+                amount,
+                currency: None,
+                timestamp: None,
+            })
+            .is_ok());
+        let tx = Transaction::Dispute {
+            id,
+            client: 2.into(),
+            amount: None,
+            timestamp: None,
+        };
+
+        // Act
+        let result = ledger.process_tx(tx);
+
+        // Assert
+        assert!(matches!(result, Err(Error::TransactionNotFound(..))));
+    }
+
+    #[test]
+    fn queries() {
+        // Setup
+        let id = Id::from(1);
+        let client = Id::from(1);
+        let amount = Decimal::new(2, 0);
+        let mut ledger = Ledger::default();
+        assert!(ledger
+            .process_tx(Transaction::Deposit {
+                id,
+                client,
+                amount,
+                currency: None,
+                timestamp: None,
+            })
+            .is_ok());
+        assert!(ledger
+            .process_tx(Transaction::Dispute {
+                id,
+                client,
+                amount: Some(Decimal::ONE),
+                timestamp: None,
+            })
+            .is_ok());
+
+        // Act
+        let account = ledger.account(client).unwrap().unwrap();
+        let transaction = ledger.transaction(id).unwrap();
+        let state = ledger.dispute_state(id).unwrap();
+
+        // Assert
+        assert_eq!(account.balance(None).available(), Decimal::ONE);
+        assert_eq!(account.balance(None).held(), Decimal::ONE);
+        assert_eq!(account.balance(None).total(), amount);
+        assert!(!account.locked());
+        assert!(matches!(
+            transaction,
+            Some((id, Transaction::Deposit { .. })) if id == 1
+        ));
+        assert_eq!(state, Some(TransactionState::Disputed(Decimal::ONE)));
+        assert!(ledger.account(2.into()).unwrap().is_none());
+        assert!(ledger.transaction(2.into()).unwrap().is_none());
+        assert!(ledger.dispute_state(2.into()).unwrap().is_none());
+    }
+
+    #[test]
+    fn out_of_order() {
+        // Setup
+        let client = Id::from(1);
+        let amount = Decimal::new(2, 0);
+        let config = LedgerConfig::builder()
+            .timestamps(TimestampPolicy::RejectOutOfOrder)
+            .build();
+        let mut ledger = Ledger::with_config(config);
 
         assert!(ledger
-            .process_tx(Transaction::Deposit { id, client, amount })
+            .process_tx(Transaction::Deposit {
+                id: 1.into(),
+                client,
+                amount,
+                currency: None,
+                timestamp: Some(10),
+            })
             .is_ok());
+        let tx = Transaction::Deposit {
+            id: 2.into(),
+            client,
+            amount,
+            currency: None,
+            timestamp: Some(9),
+        };
 
         // Act
         let result = ledger.process_tx(tx);
 
         // Assert
-        assert!(matches!(result, Err(Error::DuplicateTxId(..))));
+        assert!(matches!(result, Err(Error::OutOfOrder(id, client)) if id == 2 && client == 1));
+        let account = ledger.account(client).unwrap().unwrap();
+        assert_eq!(account.balance(None).total(), amount);
+        assert_eq!(account.last_activity(), Some(10));
+        assert!(ledger
+            .process_tx(Transaction::Withdrawal {
+                id: 3.into(),
+                client,
+                amount,
+                currency: None,
+                timestamp: None,
+            })
+            .is_ok());
+    }
+
+    fn deposit(id: TxId, timestamp: Timestamp) -> Transaction {
+        Transaction::Deposit {
+            id,
+            client: 1.into(),
+            amount: Decimal::new(2, 0),
+            currency: None,
+            timestamp: Some(timestamp),
+        }
+    }
+
+    fn dispute(id: TxId, timestamp: Timestamp) -> Transaction {
+        Transaction::Dispute {
+            id,
+            client: 1.into(),
+            amount: None,
+            timestamp: Some(timestamp),
+        }
+    }
+
+    #[test]
+    fn dispute_window_transactions() {
+        // Setup
+        let config = LedgerConfig::builder()
+            .disputes(DisputePolicy {
+                window: Period::Transactions(2),
+                expiry: None,
+            })
+            .build();
+        let mut ledger = Ledger::with_config(config);
+        for id in 1..=3 {
+            assert!(ledger.process_tx(deposit(id.into(), 0)).is_ok());
+        }
+
+        // Act
+        let expired = ledger.process_tx(dispute(1.into(), 0));
+        let result = ledger.process_tx(dispute(2.into(), 0));
+
+        // Assert
+        assert!(matches!(expired, Err(Error::DisputeWindowExpired(id)) if id == 1));
+        assert!(result.is_ok());
+        assert_eq!(
+            ledger.dispute_state(1.into()).unwrap(),
+            Some(TransactionState::Normal)
+        );
+    }
+
+    #[test]
+    fn dispute_window_time() {
+        // Setup
+        let config = LedgerConfig::builder()
+            .disputes(DisputePolicy {
+                window: Period::Time(10),
+                expiry: None,
+            })
+            .build();
+        let mut ledger = Ledger::with_config(config);
+        assert!(ledger.process_tx(deposit(1.into(), 100)).is_ok());
+        assert!(ledger.process_tx(deposit(2.into(), 105)).is_ok());
+
+        // Act
+        let expired = ledger.process_tx(dispute(1.into(), 111));
+        let result = ledger.process_tx(dispute(2.into(), 111));
+
+        // Assert
+        assert!(matches!(expired, Err(Error::DisputeWindowExpired(id)) if id == 1));
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn dispute_expiry() {
+        // Setup
+        let config = LedgerConfig::builder()
+            .disputes(DisputePolicy {
+                window: Period::Unlimited,
+                expiry: Some(DisputeExpiry {
+                    after: Period::Time(10),
+                    action: ExpiryAction::Chargeback,
+                }),
+            })
+            .build();
+        let mut ledger = Ledger::with_config(config);
+        assert!(ledger.process_tx(deposit(1.into(), 100)).is_ok());
+        assert!(ledger.process_tx(deposit(2.into(), 100)).is_ok());
+        assert!(ledger.process_tx(dispute(1.into(), 100)).is_ok());
+        assert!(ledger.process_tx(deposit(3.into(), 110)).is_ok());
+
+        // Act
+        let result = ledger.process_tx(deposit(4.into(), 111));
+
+        // Assert
+        assert!(matches!(result, Err(Error::Locked(client)) if client == 1));
+        assert_eq!(
+            ledger.dispute_state(1.into()).unwrap(),
+            Some(TransactionState::Chargedback)
+        );
+        let account = ledger.account(1.into()).unwrap().unwrap();
+        assert_eq!(account.balance(None).total(), Decimal::new(4, 0));
+        assert_eq!(account.open_disputes().count(), 0);
+    }
+
+    #[test]
+    fn expiry_rejected_transaction() {
+        // Setup
+        let config = LedgerConfig::builder()
+            .timestamps(TimestampPolicy::RejectOutOfOrder)
+            .disputes(DisputePolicy {
+                window: Period::Unlimited,
+                expiry: Some(DisputeExpiry {
+                    after: Period::Time(10),
+                    action: ExpiryAction::Chargeback,
+                }),
+            })
+            .build();
+        let mut ledger = Ledger::with_config(config);
+        assert!(ledger.process_tx(deposit(1.into(), 1)).is_ok());
+        assert!(ledger.process_tx(dispute(1.into(), 1)).is_ok());
+        let tx = Transaction::Resolve {
+            id: 99.into(),
+            client: 1.into(),
+            timestamp: Some(1_000_000),
+        };
+
+        // Act
+        let result = ledger.process_tx(tx);
+
+        // Assert
+        assert!(
+            matches!(result, Err(Error::TransactionNotFound(id, client)) if id == 99 && client == 1)
+        );
+        let account = ledger.account(1.into()).unwrap().unwrap();
+        assert!(!account.locked());
+        assert_eq!(account.last_activity(), Some(1));
+        assert_eq!(account.open_disputes().collect::<Vec<_>>(), [1]);
+        assert_eq!(ledger.history(1.into()).count(), 2);
+        assert!(ledger.process_tx(deposit(2.into(), 5)).is_ok());
+    }
+
+    #[test]
+    fn expire_disputes() {
+        // Setup
+        let config = LedgerConfig::builder()
+            .disputes(DisputePolicy {
+                window: Period::Unlimited,
+                expiry: Some(DisputeExpiry {
+                    after: Period::Time(10),
+                    action: ExpiryAction::Resolve,
+                }),
+            })
+            .build();
+        let mut ledger = Ledger::with_config(config);
+        assert!(ledger.process_tx(deposit(1.into(), 100)).is_ok());
+        assert!(ledger.process_tx(dispute(1.into(), 100)).is_ok());
+
+        // Act
+        let early = ledger.expire_disputes(110);
+        let state = ledger.dispute_state(1.into()).unwrap();
+        let result = ledger.expire_disputes(111);
+
+        // Assert
+        assert!(early.is_ok());
+        assert_eq!(state, Some(TransactionState::Disputed(Decimal::new(2, 0))));
+        assert!(result.is_ok());
+        assert_eq!(
+            ledger.dispute_state(1.into()).unwrap(),
+            Some(TransactionState::Normal)
+        );
+        let account = ledger.account(1.into()).unwrap().unwrap();
+        assert_eq!(account.balance(None).available(), Decimal::new(2, 0));
+        assert_eq!(ledger.history(1.into()).count(), 3);
+    }
+
+    fn authorize(id: TxId, timestamp: Timestamp) -> Transaction {
+        Transaction::Authorize {
+            id,
+            client: 1.into(),
+            amount: Decimal::ONE,
+            currency: None,
+            timestamp: Some(timestamp),
+        }
+    }
+
+    #[test]
+    fn authorization_expiry() {
+        // Setup
+        let config = LedgerConfig::builder()
+            .authorization_expiry(Period::Time(10))
+            .build();
+        let mut ledger = Ledger::with_config(config);
+        assert!(ledger.process_tx(deposit(1.into(), 100)).is_ok());
+        assert!(ledger.process_tx(authorize(2.into(), 100)).is_ok());
+        assert!(ledger.process_tx(authorize(3.into(), 105)).is_ok());
+
+        // Act
+        let early = ledger.expire_authorizations(110);
+        let state = ledger.dispute_state(2.into()).unwrap();
+        let result = ledger.expire_authorizations(111);
+        let account = ledger.account(1.into()).unwrap().unwrap();
+        let capture = ledger.process_tx(Transaction::Capture {
+            id: 3.into(),
+            client: 1.into(),
+            amount: None,
+            timestamp: Some(116),
+        });
+
+        // Assert
+        assert!(early.is_ok());
+        assert_eq!(state, Some(TransactionState::Authorized(Decimal::ONE)));
+        assert!(result.is_ok());
+        assert_eq!(
+            ledger.dispute_state(2.into()).unwrap(),
+            Some(TransactionState::Voided)
+        );
+        assert_eq!(account.balance(None).available(), Decimal::ONE);
+        assert_eq!(account.balance(None).authorized(), Decimal::ONE);
+        assert!(matches!(capture, Err(Error::NotAuthorized(id)) if id == 3));
+        let account = ledger.account(1.into()).unwrap().unwrap();
+        assert_eq!(account.balance(None).available(), Decimal::new(2, 0));
+        assert_eq!(account.balance(None).authorized(), Decimal::ZERO);
+        assert_eq!(ledger.history(1.into()).count(), 5);
+    }
+
+    #[test]
+    fn config_amounts() {
+        // Setup
+        let config = LedgerConfig::builder()
+            .precision(2)
+            .zero_amounts(true)
+            .build();
+        let mut ledger = Ledger::with_config(config);
+
+        // Act
+        let zero = ledger.process_tx(Transaction::Deposit {
+            id: 1.into(),
+            client: 1.into(),
+            amount: Decimal::ZERO,
+            currency: None,
+            timestamp: None,
+        });
+        let rounded = ledger.process_tx(Transaction::Deposit {
+            id: 2.into(),
+            client: 1.into(),
+            amount: Decimal::new(12345, 4),
+            currency: None,
+            timestamp: None,
+        });
+
+        // Assert
+        assert!(zero.is_ok());
+        assert!(rounded.is_ok());
+        let account = ledger.account(1.into()).unwrap().unwrap();
+        assert_eq!(account.balance(None).total(), Decimal::new(123, 2));
+    }
+
+    #[test]
+    fn config_precision_changed() {
+        // Setup
+        let mut ledger = Ledger::new();
+        let deposit = Transaction::Deposit {
+            id: 1.into(),
+            client: 1.into(),
+            amount: Decimal::new(12345, 4),
+            currency: None,
+            timestamp: None,
+        };
+        assert!(ledger.process_tx(deposit).is_ok());
+        ledger.set_config(
+            LedgerConfig::builder()
+                .precision(2)
+                .rounding(Rounding::Reject)
+                .build(),
+        );
+
+        // Act
+        let disputed = ledger.process_tx(Transaction::Dispute {
+            id: 1.into(),
+            client: 1.into(),
+            amount: None,
+            timestamp: None,
+        });
+        let held = ledger
+            .account(1.into())
+            .unwrap()
+            .unwrap()
+            .balance(None)
+            .held();
+        let chargeback = ledger.process_tx(Transaction::Chargeback {
+            id: 1.into(),
+            client: 1.into(),
+            timestamp: None,
+        });
+
+        // Assert
+        assert!(disputed.is_ok());
+        assert!(chargeback.is_ok());
+        assert_eq!(held, Decimal::new(12345, 4));
+        let account = ledger.account(1.into()).unwrap().unwrap();
+        assert_eq!(account.balance(None).available(), Decimal::ZERO);
+        assert_eq!(account.balance(None).total(), Decimal::ZERO);
+    }
+
+    #[test]
+    fn config_failed_accounts() {
+        // Setup
+        let config = LedgerConfig::builder()
+            .create_failed_accounts(false)
+            .build();
+        let mut ledger = Ledger::with_config(config);
+
+        // Act
+        let result = ledger.process_tx(Transaction::Withdrawal {
+            id: 1.into(),
+            client: 1.into(),
+            amount: Decimal::ONE,
+            currency: None,
+            timestamp: None,
+        });
+
+        // Assert
+        assert!(matches!(result, Err(Error::InsufficientFunds(..))));
+        assert!(ledger.account(1.into()).unwrap().is_none());
+    }
+
+    #[test]
+    fn config_tx_ids() {
+        // Setup
+        let withdrawal = Transaction::Withdrawal {
+            id: 2.into(),
+            client: 1.into(),
+            amount: Decimal::new(3, 0),
+            currency: None,
+            timestamp: None,
+        };
+        let config = LedgerConfig::builder().reserve_failed_tx_ids(false).build();
+        let mut reserved = Ledger::default();
+        let mut unreserved = Ledger::with_config(config);
+        for ledger in [&mut reserved, &mut unreserved] {
+            assert!(ledger.process_tx(deposit(1.into(), 0)).is_ok());
+            assert!(ledger.process_tx(withdrawal.clone()).is_err());
+            assert!(ledger.process_tx(deposit(3.into(), 0)).is_ok());
+        }
+
+        // Act
+        let reserved = reserved.process_tx(withdrawal.clone());
+        let unreserved_retry = unreserved.process_tx(withdrawal.clone());
+        let unreserved_replay = unreserved.process_tx(withdrawal);
+
+        // Assert
+        assert!(matches!(reserved, Err(Error::DuplicateTxId(id)) if id == 2));
+        assert!(matches!(unreserved_retry, Ok(Outcome::Processed)));
+        assert!(matches!(unreserved_replay, Ok(Outcome::Duplicate)));
+        let account = unreserved.account(1.into()).unwrap().unwrap();
+        assert_eq!(account.balance(None).total(), Decimal::ONE);
+    }
+
+    #[test]
+    fn config_disputes() {
+        // Setup
+        let config = LedgerConfig::builder()
+            .lock_on_chargeback(false)
+            .redispute(false)
+            .unique_tx_ids(false)
+            .build();
+        let mut ledger = Ledger::with_config(config);
+        for id in 1..=2 {
+            assert!(ledger.process_tx(deposit(id.into(), 0)).is_ok());
+            assert!(ledger.process_tx(dispute(id.into(), 0)).is_ok());
+        }
+        assert!(ledger
+            .process_tx(Transaction::Resolve {
+                id: 1.into(),
+                client: 1.into(),
+                timestamp: None,
+            })
+            .is_ok());
+
+        // Act
+        let redispute = ledger.process_tx(dispute(1.into(), 0));
+        let chargeback = ledger.process_tx(Transaction::Chargeback {
+            id: 2.into(),
+            client: 1.into(),
+            timestamp: None,
+        });
+        let duplicate = ledger.process_tx(deposit(1.into(), 0));
+        let open = ledger.process_tx(deposit(2.into(), 0));
+
+        // Assert
+        assert!(matches!(redispute, Err(Error::AlreadyDisputed(id)) if id == 1));
+        assert!(chargeback.is_ok());
+        assert!(duplicate.is_ok());
+        assert!(open.is_ok());
+        let account = ledger.account(1.into()).unwrap().unwrap();
+        assert!(!account.locked());
+        assert_eq!(account.balance(None).total(), Decimal::new(6, 0));
+    }
+
+    #[test]
+    fn config_tx_ids_reuse() {
+        // Setup
+        let config = LedgerConfig::builder().unique_tx_ids(false).build();
+        let mut ledger = Ledger::with_config(config);
+        let other = Transaction::Deposit {
+            id: 1.into(),
+            client: 2.into(),
+            amount: Decimal::new(10, 0),
+            currency: None,
+            timestamp: None,
+        };
+        assert!(ledger.process_tx(deposit(1.into(), 0)).is_ok());
+        assert!(ledger.process_tx(dispute(1.into(), 0)).is_ok());
+
+        // Act
+        let disputed = ledger.process_tx(deposit(1.into(), 0));
+        let other_disputed = ledger.process_tx(other.clone());
+        assert!(ledger
+            .process_tx(Transaction::Resolve {
+                id: 1.into(),
+                client: 1.into(),
+                timestamp: None,
+            })
+            .is_ok());
+        let other_client = ledger.process_tx(other);
+
+        // Assert
+        assert!(matches!(disputed, Err(Error::ConflictingTxId { .. })));
+        assert!(matches!(other_disputed, Err(Error::ConflictingTxId { .. })));
+        assert!(matches!(other_client, Err(Error::ConflictingTxId { .. })));
+        let account = ledger.account(1.into()).unwrap().unwrap();
+        assert_eq!(account.balance(None).available(), Decimal::new(2, 0));
+        assert_eq!(account.balance(None).held(), Decimal::ZERO);
+        assert!(account.open_disputes().next().is_none());
+        assert_eq!(ledger.transaction(1.into()).unwrap().unwrap().0, 1);
+    }
+
+    fn transfer(id: TxId, amount: Decimal) -> Transaction {
+        Transaction::Transfer {
+            id,
+            from: 1.into(),
+            to: 2.into(),
+            amount,
+            currency: None,
+            timestamp: None,
+        }
+    }
+
+    #[test]
+    fn transfer_atomic() {
+        // Setup
+        let mut ledger = Ledger::default();
+        let locked = Transaction::Chargeback {
+            id: 2.into(),
+            client: 2.into(),
+            timestamp: None,
+        };
+
+        assert!(ledger.process_tx(deposit(1.into(), 0)).is_ok());
+        assert!(ledger
+            .process_tx(Transaction::Deposit {
+                id: 2.into(),
+                client: 2.into(),
+                amount: Decimal::ONE,
+                currency: None,
+                timestamp: None,
+            })
+            .is_ok());
+
+        // Act
+        let insufficient = ledger.process_tx(transfer(3.into(), Decimal::new(3, 0)));
+        let own = ledger.process_tx(Transaction::Transfer {
+            id: 4.into(),
+            from: 1.into(),
+            to: 1.into(),
+            amount: Decimal::ONE,
+            currency: None,
+            timestamp: None,
+        });
+        let transferred = ledger.process_tx(transfer(5.into(), Decimal::ONE));
+        assert!(ledger.process_tx(dispute(2.into(), 0)).is_err());
+        assert!(ledger
+            .process_tx(Transaction::Dispute {
+                id: 2.into(),
+                client: 2.into(),
+                amount: None,
+                timestamp: None,
+            })
+            .is_ok());
+        assert!(ledger.process_tx(locked).is_ok());
+        let to_locked = ledger.process_tx(transfer(6.into(), Decimal::ONE));
+
+        // Assert
+        assert!(matches!(insufficient, Err(Error::InsufficientFunds(..))));
+        assert!(matches!(own, Err(Error::SelfTransfer(client)) if client == 1));
+        assert!(matches!(transferred, Ok(Outcome::Processed)));
+        assert!(matches!(to_locked, Err(Error::Locked(client)) if client == 2));
+        let from = ledger.account(1.into()).unwrap().unwrap();
+        let to = ledger.account(2.into()).unwrap().unwrap();
+        assert_eq!(from.balance(None).available(), Decimal::ONE);
+        assert_eq!(to.balance(None).available(), Decimal::ONE);
+        assert_eq!(to.balance(None).total(), Decimal::ONE);
+        assert_eq!(ledger.history(1.into()).count(), 2);
+        assert_eq!(ledger.history(2.into()).count(), 4);
+    }
+
+    #[test]
+    fn transfer_dispute() {
+        // Setup
+        let mut ledger = Ledger::default();
+        let amount = Decimal::new(2, 0);
+        let chargeback = Transaction::Chargeback {
+            id: 2.into(),
+            client: 1.into(),
+            timestamp: None,
+        };
+
+        assert!(ledger.process_tx(deposit(1.into(), 0)).is_ok());
+        assert!(ledger.process_tx(transfer(2.into(), amount)).is_ok());
+        assert!(ledger
+            .process_tx(Transaction::Dispute {
+                id: 2.into(),
+                client: 2.into(),
+                amount: None,
+                timestamp: None,
+            })
+            .is_err());
+
+        // Act
+        let disputed = ledger.process_tx(dispute(2.into(), 0));
+        let from = ledger.account(1.into()).unwrap().unwrap();
+        let to = ledger.account(2.into()).unwrap().unwrap();
+        let charged_back = ledger.process_tx(chargeback);
+
+        // Assert
+        assert!(disputed.is_ok());
+        assert_eq!(from.balance(None).held(), amount);
+        assert_eq!(from.balance(None).total(), amount);
+        assert_eq!(to.balance(None).available(), Decimal::ZERO);
+        assert_eq!(to.balance(None).held(), amount);
+        assert!(charged_back.is_ok());
+        let from = ledger.account(1.into()).unwrap().unwrap();
+        let to = ledger.account(2.into()).unwrap().unwrap();
+        assert_eq!(from.balance(None).available(), amount);
+        assert_eq!(from.balance(None).held(), Decimal::ZERO);
+        assert!(from.locked());
+        assert_eq!(to.balance(None).total(), Decimal::ZERO);
+        assert!(!to.locked());
+        assert_eq!(
+            ledger.dispute_state(2.into()).unwrap(),
+            Some(TransactionState::Chargedback)
+        );
+    }
+
+    #[test]
+    fn transfer_dispute_locked() {
+        // Setup
+        let mut ledger = Ledger::default();
+        let amount = Decimal::new(2, 0);
+        let locking = [
+            Transaction::Deposit {
+                id: 3.into(),
+                client: 2.into(),
+                amount: Decimal::ONE,
+                currency: None,
+                timestamp: None,
+            },
+            Transaction::Dispute {
+                id: 3.into(),
+                client: 2.into(),
+                amount: None,
+                timestamp: None,
+            },
+            Transaction::Chargeback {
+                id: 3.into(),
+                client: 2.into(),
+                timestamp: None,
+            },
+        ];
+
+        assert!(ledger.process_tx(deposit(1.into(), 0)).is_ok());
+        assert!(ledger.process_tx(transfer(2.into(), amount)).is_ok());
+        for tx in locking {
+            assert!(ledger.process_tx(tx).is_ok());
+        }
+
+        // Act
+        let disputed = ledger.process_tx(dispute(2.into(), 0));
+        let charged_back = ledger.process_tx(Transaction::Chargeback {
+            id: 2.into(),
+            client: 1.into(),
+            timestamp: None,
+        });
+
+        // Assert
+        assert!(disputed.is_ok());
+        assert!(charged_back.is_ok());
+        let to = ledger.account(2.into()).unwrap().unwrap();
+        assert!(to.locked());
+        assert_eq!(to.balance(None).total(), Decimal::ZERO);
+        assert_eq!(
+            ledger.dispute_state(2.into()).unwrap(),
+            Some(TransactionState::Chargedback)
+        );
+    }
+
+    #[test]
+    fn validators() {
+        // Setup
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut ledger = Ledger::default();
+        for n in 0..2 {
+            let calls = calls.clone();
+            ledger.add_validator(move |tx: &Transaction, account: &Account| {
+                calls.lock().unwrap().push(n);
+                match tx {
+                    Transaction::Deposit { amount, .. }
+                        if n == 0 && *amount + account.balance(None).total() > Decimal::TEN =>
+                    {
+                        Err("balance above 10".into())
+                    }
+                    _ => Ok(()),
+                }
+            });
+        }
+        assert!(ledger.process_tx(deposit(1.into(), 0)).is_ok());
+
+        // Act
+        let result = ledger.process_tx(Transaction::Deposit {
+            id: 2.into(),
+            client: 1.into(),
+            amount: Decimal::TEN,
+            currency: None,
+            timestamp: None,
+        });
+
+        // Assert
+        match result {
+            Err(Error::Rejected(id, e)) if id == 2 => assert_eq!(e.to_string(), "balance above 10"),
+            _ => panic!("unexpected result {result:?}"),
+        }
+        assert_eq!(*calls.lock().unwrap(), [0, 1, 0]);
+        let account = ledger.account(1.into()).unwrap().unwrap();
+        assert_eq!(account.balance(None).total(), Decimal::new(2, 0));
+        assert_eq!(account.count(TransactionKind::Deposit), 1);
+        let mut fork = ledger.fork();
+        assert!(matches!(
+            fork.process_tx(Transaction::Deposit {
+                id: 3.into(),
+                client: 1.into(),
+                amount: Decimal::TEN,
+                currency: None,
+                timestamp: None,
+            }),
+            Err(Error::Rejected(id, ..)) if id == 3
+        ));
+    }
+
+    #[test]
+    fn observers() {
+        // Setup
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut ledger = Ledger::default();
+        let observed = events.clone();
+        ledger.add_observer(move |event: &Event| {
+            let event = match event {
+                Event::Deposited { id, .. } => format!("deposited {id}"),
+                Event::Rejected { tx, error } => format!("rejected {}: {error}", tx.id()),
+                _ => format!("{event:?}"),
+            };
+            observed.lock().unwrap().push(event);
+        });
+
+        // Act
+        assert!(ledger.process_tx(deposit(1.into(), 0)).is_ok());
+        assert!(ledger.process_tx(deposit(1.into(), 1)).is_err());
+        assert!(ledger.fork().process_tx(deposit(2.into(), 0)).is_ok());
+        assert!(ledger
+            .apply_batch([deposit(3.into(), 0), deposit(1.into(), 1)])
+            .is_err());
+        assert!(ledger.apply_batch([deposit(4.into(), 0)]).is_ok());
+
+        // Assert
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                "deposited 1",
+                "rejected 1: Transaction with ID 1 conflicts with an earlier transaction with the same ID!",
+                "rejected 1: Transaction with ID 1 conflicts with an earlier transaction with the same ID!",
+                "deposited 4",
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_batch() {
+        // Setup
+        let client = Id::from(1);
+        let amount = Decimal::new(2, 0);
+        let mut ledger = Ledger::default();
+        let batch = vec![
+            Transaction::Deposit {
+                id: 1.into(),
+                client,
+                amount,
+                currency: None,
+                timestamp: None,
+            },
+            Transaction::Withdrawal {
+                id: 2.into(),
+                client,
+                amount,
+                currency: None,
+                timestamp: None,
+            },
+            Transaction::Dispute {
+                id: 1.into(),
+                client,
+                amount: None,
+                timestamp: None,
+            },
+        ];
+
+        // Act
+        let result = ledger.apply_batch(batch);
+
+        // Assert
+        assert!(result.is_ok());
+        let account = ledger.storage().accounts.get(&client).unwrap();
+        let rows = account.rows().collect::<Vec<_>>();
+        assert_eq!(rows[0].available, -amount);
+        assert_eq!(rows[0].held, amount);
+        assert!(ledger.storage().transactions.contains_key(&2.into()));
+        assert!(ledger.storage().tx_ids.contains(&2.into()));
+    }
+
+    #[test]
+    fn apply_batch_rollback() {
+        // Setup
+        let client = Id::from(1);
+        let amount = Decimal::new(2, 0);
+        let mut ledger = Ledger::default();
+        assert!(ledger
+            .process_tx(Transaction::Deposit {
+                id: 1.into(),
+                client,
+                amount,
+                currency: None,
+                timestamp: None,
+            })
+            .is_ok());
+        let batch = vec![
+            Transaction::Dispute {
+                id: 1.into(),
+                client,
+                amount: None,
+                timestamp: None,
+            },
+            Transaction::Deposit {
+                id: 2.into(),
+                client: 2.into(),
+                amount,
+                currency: None,
+                timestamp: None,
+            },
+            Transaction::Deposit {
+                id: 1.into(),
+                client,
+                amount: amount * amount,
+                currency: None,
+                timestamp: None,
+            },
+        ];
+
+        // Act
+        let result = ledger.apply_batch(batch);
+
+        // Assert
+        assert!(matches!(result, Err((2, Error::ConflictingTxId { .. }))));
+        assert_eq!(ledger.get_accounts().count(), 1);
+        let account = ledger.storage().accounts.get(&client).unwrap();
+        let rows = account.rows().collect::<Vec<_>>();
+        assert_eq!(rows[0].available, amount);
+        assert_eq!(rows[0].held, Decimal::ZERO);
+        let record = ledger.storage().transactions.get(&1.into()).unwrap();
+        assert!(matches!(record.state, TransactionState::Normal));
+        assert!(!ledger.storage().tx_ids.contains(&2.into()));
+    }
+
+    /// Storage that writes single items, but fails to commit changes
+    #[derive(Default)]
+    struct FailingCommit(MemoryStorage);
+
+    impl Storage for FailingCommit {
+        fn account(&self, client: ClientId) -> Result<Option<Account>, Error> {
+            self.0.account(client)
+        }
+
+        fn put_account(&mut self, account: Account) -> Result<(), Error> {
+            self.0.put_account(account)
+        }
+
+        fn accounts(&self) -> StorageIter<'_, Account> {
+            self.0.accounts()
+        }
+
+        fn transaction(&self, id: TxId) -> Result<Option<TxRecord>, Error> {
+            self.0.transaction(id)
+        }
+
+        fn put_transaction(&mut self, id: TxId, record: TxRecord) -> Result<(), Error> {
+            self.0.put_transaction(id, record)
+        }
+
+        fn transactions(&self) -> StorageIter<'_, (TxId, TxRecord)> {
+            self.0.transactions()
+        }
+
+        fn contains_tx_id(&self, id: TxId) -> Result<bool, Error> {
+            self.0.contains_tx_id(id)
+        }
+
+        fn insert_tx_id(&mut self, id: TxId) -> Result<bool, Error> {
+            self.0.insert_tx_id(id)
+        }
+
+        fn tx_ids(&self) -> StorageIter<'_, TxId> {
+            self.0.tx_ids()
+        }
+
+        fn history(&self, client: ClientId) -> StorageIter<'_, HistoryEntry> {
+            self.0.history(client)
+        }
+
+        fn put_history(&mut self, entry: HistoryEntry) -> Result<(), Error> {
+            self.0.put_history(entry)
+        }
+
+        fn histories(&self) -> StorageIter<'_, HistoryEntry> {
+            self.0.histories()
+        }
+
+        fn commit(&mut self, _: MemoryStorage) -> Result<(), Error> {
+            Err(Error::Storage("commit failed".to_string()))
+        }
+    }
+
+    #[test]
+    fn commit_failed() {
+        // Setup
+        let mut ledger = Ledger::with_storage(FailingCommit::default());
+        let batch = [(1, 1), (2, 2)].map(|(id, client)| Transaction::Deposit {
+            id: Id::from(id),
+            client: Id::from(client),
+            amount: Decimal::ONE,
+            currency: None,
+            timestamp: None,
+        });
+
+        assert!(ledger.process_tx(deposit(3.into(), 0)).is_ok());
+
+        // Act
+        let batch = ledger.apply_batch(batch);
+        let transfer = ledger.process_tx(transfer(4.into(), Decimal::ONE));
+
+        // Assert
+        assert!(matches!(batch, Err((2, Error::Storage(..)))));
+        assert!(matches!(transfer, Err(Error::Storage(..))));
+        let accounts = ledger
+            .get_accounts()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].balance(None).available(), Decimal::new(2, 0));
+        assert!(!ledger.storage().0.contains_tx_id(1.into()).unwrap());
+        assert!(ledger.storage().0.transaction(4.into()).unwrap().is_none());
+        assert_eq!(ledger.history(1.into()).count(), 1);
+    }
+
+    #[test]
+    fn snapshot_restore() {
+        // Setup
+        let id = Id::from(1);
+        let client = Id::from(1);
+        let amount = Decimal::new(2, 0);
+        let mut ledger = Ledger::default();
+
+        assert!(ledger
+            .process_tx(Transaction::Deposit {
+                id,
+                client,
+                amount,
+                currency: Some("EUR".parse().unwrap()),
+                timestamp: None,
+            })
+            .is_ok());
+        let mut snapshot = Vec::new();
+
+        // Act
+        let result = ledger
+            .snapshot(&mut snapshot)
+            .and_then(|_| Ledger::restore(snapshot.as_slice()));
+
+        // Assert
+        assert!(result.is_ok());
+        let mut ledger = result.unwrap();
+        let rows = ledger
+            .get_accounts()
+            .flat_map(|a| a.unwrap().rows().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].available, amount);
+        assert!(ledger
+            .process_tx(Transaction::Dispute {
+                id,
+                client,
+                amount: None,
+                timestamp: None,
+            })
+            .is_ok());
+        let result = ledger.process_tx(Transaction::Deposit {
+            id,
+            client: 2.into(),
+            amount,
+            currency: None,
+            timestamp: None,
+        });
+        assert!(matches!(result, Err(Error::ConflictingTxId { .. })));
+        let sequences = ledger
+            .history(client)
+            .map(|e| e.unwrap().sequence())
+            .collect::<Vec<_>>();
+        assert_eq!(sequences, [0, 1]);
+    }
+
+    #[test]
+    fn snapshot_ids() {
+        // Setup
+        let id = Id::from_uuid(u128::MAX);
+        let client = "acct-17".parse::<ClientId>().unwrap();
+        let mut ledger = Ledger::default();
+        assert!(ledger
+            .process_tx(Transaction::Deposit {
+                id,
+                client,
+                amount: Decimal::ONE,
+                currency: None,
+                timestamp: None,
+            })
+            .is_ok());
+        let mut snapshot = Vec::new();
+
+        // Act
+        let result = ledger
+            .snapshot(&mut snapshot)
+            .and_then(|_| Ledger::restore(snapshot.as_slice()));
+
+        // Assert
+        let ledger = result.unwrap();
+        assert_eq!(ledger.transaction(id).unwrap().unwrap().0, client);
+        assert!(ledger.storage().tx_ids.contains(&id));
+        assert_eq!(ledger.history(client).count(), 1);
+    }
+
+    #[test]
+    fn restore_invalid() {
+        // Act
+        let result = Ledger::restore("not a snapshot".as_bytes());
+
+        // Assert
+        assert!(matches!(result, Err(Error::Snapshot(..))));
     }
 }
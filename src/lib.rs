@@ -1,11 +1,40 @@
 pub mod error;
+pub mod storage;
 
 mod account;
+#[cfg(feature = "async")]
+mod async_ledger;
+mod config;
+mod currency;
+mod dispute;
+mod event;
+mod fork;
+mod history;
+mod id;
 mod ledger;
+mod parallel;
+mod snapshot;
 mod transaction;
+mod validator;
 
-pub type ClientId = u16;
-pub type TxId = u32;
+/// ID of a client, a number, a UUID or a string
+pub type ClientId = Id;
+/// ID of a transaction, a number, a UUID or a string
+pub type TxId = Id;
+/// Time of a transaction, in a unit chosen by the user such as seconds since the Unix epoch
+pub type Timestamp = u64;
 
-pub use ledger::Ledger;
-pub use transaction::Transaction;
+pub use account::{Account, AccountRow, AccountStatus, Balance};
+#[cfg(feature = "async")]
+pub use async_ledger::AsyncLedger;
+pub use config::{LedgerConfig, LedgerConfigBuilder, Rounding, TimestampPolicy};
+pub use currency::{Currency, InvalidCurrency};
+pub use dispute::{DisputeExpiry, DisputePolicy, ExpiryAction, Period};
+pub use event::{BalanceChange, Event, LedgerObserver};
+pub use fork::RowChange;
+pub use history::{History, HistoryEntry};
+pub use id::{Id, InvalidId};
+pub use ledger::{Ledger, Outcome};
+pub use parallel::ParallelLedger;
+pub use transaction::{Transaction, TransactionKind, TransactionState, TxRecord};
+pub use validator::{TransactionValidator, ValidatorError};
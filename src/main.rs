@@ -1,10 +1,21 @@
 mod csv_record;
 
 use clap::Parser;
-use csv::{Error, Trim};
-use csv_record::TransactionRecord;
-use ledger_rs::Ledger;
-use std::{collections::BTreeMap, io};
+use csv::{Error, StringRecord, Trim};
+use csv_record::{ClientRecord, RecordFailure, TransactionRecord};
+use ledger_rs::{
+    AccountRow, AccountStatus, ClientId, Currency, Event, Ledger, LedgerConfig,
+    LedgerConfigBuilder, ParallelLedger, RowChange, Timestamp, TimestampPolicy, Transaction,
+};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    error,
+    fs::{self, File},
+    io::{self, Write},
+    num::NonZeroUsize,
+};
 use tracing::{debug, warn, Level};
 
 #[derive(Debug, Parser)]
@@ -19,9 +30,118 @@ struct Cli {
     /// Sort output accounts on ClientId
     #[clap(short, long, value_parser, default_value = "false")]
     sort: bool,
+    /// Restore the ledger from a snapshot before processing
+    #[clap(long, value_parser)]
+    state_in: Option<String>,
+    /// Write a snapshot of the ledger after processing
+    #[clap(long, value_parser)]
+    state_out: Option<String>,
+    /// Number of worker threads, transactions are sharded on ClientId
+    #[clap(short, long, value_parser, default_value = "1")]
+    threads: NonZeroUsize,
+    /// Process the input on a fork of the ledger and output the changed balances
+    #[clap(
+        long,
+        value_parser,
+        default_value = "false",
+        conflicts_with = "state-out"
+    )]
+    dry_run: bool,
+    /// Reject transactions with a timestamp before the last activity of their account
+    #[clap(long, value_parser, default_value = "false")]
+    reject_out_of_order: bool,
+    /// Load the ledger configuration from a TOML file
+    #[clap(short, long, value_parser)]
+    config: Option<String>,
+    /// Set the credit limits of clients from a CSV file before processing
+    #[clap(long, value_parser)]
+    clients: Option<String>,
+    /// Write the failed input records with their line number and error code to a CSV file
+    #[clap(long, value_parser)]
+    rejects: Option<String>,
 }
 
-fn main() -> Result<(), Error> {
+/// Output row of a dry run, `-` for the balances before and `+` for the balances after
+#[derive(Debug, Serialize)]
+struct DiffRow {
+    change: char,
+    client: ClientId,
+    currency: Option<Currency>,
+    available: Decimal,
+    held: Decimal,
+    authorized: Decimal,
+    total: Decimal,
+    locked: bool,
+    status: AccountStatus,
+    last_activity: Option<Timestamp>,
+    credit_limit: Decimal,
+    credit_used: Decimal,
+}
+
+/// Failed records of the input file, for the rejects report
+struct Rejects {
+    enabled: bool,
+    headers: StringRecord,
+    rows: Vec<(u64, StringRecord, RecordFailure)>,
+}
+
+impl Rejects {
+    fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            headers: StringRecord::new(),
+            rows: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, record: &StringRecord, failure: RecordFailure) {
+        warn!("{failure}");
+        if self.enabled {
+            let line = record.position().map_or(0, |position| position.line());
+            self.rows.push((line, record.clone(), failure));
+        }
+    }
+
+    /// Writes the failed records ordered on line number, with the code and message of the
+    /// failure before the original fields
+    fn write(mut self, path: &str) -> Result<(), Error> {
+        self.rows.sort_by_key(|(line, ..)| *line);
+        let mut wtr = csv::WriterBuilder::new().flexible(true).from_path(path)?;
+        let columns = ["line", "code", "message"];
+        wtr.write_record(columns.into_iter().chain(self.headers.iter()))?;
+        for (line, fields, failure) in self.rows {
+            let columns = [
+                line.to_string(),
+                failure.code().to_string(),
+                failure.to_string(),
+            ];
+            wtr.write_record(columns.iter().map(String::as_str).chain(fields.iter()))?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+impl DiffRow {
+    fn new(change: char, row: AccountRow) -> Self {
+        Self {
+            change,
+            client: row.client,
+            currency: row.currency,
+            available: row.available,
+            held: row.held,
+            authorized: row.authorized,
+            total: row.total,
+            locked: row.locked,
+            status: row.status,
+            last_activity: row.last_activity,
+            credit_limit: row.credit_limit,
+            credit_used: row.credit_used,
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn error::Error>> {
     let cli = Cli::parse();
     if cli.debug {
         tracing_subscriber::fmt()
@@ -29,9 +149,50 @@ fn main() -> Result<(), Error> {
             .init();
     }
 
-    let ledger = process_file(&cli.path)?;
+    let mut ledger = match &cli.state_in {
+        Some(path) => Ledger::restore(io::BufReader::new(File::open(path)?))?,
+        None => Ledger::new(),
+    };
+    let mut config = match &cli.config {
+        Some(path) => toml::from_str(&fs::read_to_string(path)?)?,
+        None => LedgerConfig::default(),
+    };
+    if cli.reject_out_of_order {
+        config = LedgerConfigBuilder::from(config)
+            .timestamps(TimestampPolicy::RejectOutOfOrder)
+            .build();
+    }
+    ledger.set_config(config);
+    if cli.debug {
+        ledger.add_observer(|event: &Event| debug!("{event:?}"));
+    }
+    if let Some(path) = &cli.clients {
+        read_clients(path, &mut ledger)?;
+    }
+    let mut rejects = Rejects::new(cli.rejects.is_some());
+    if cli.dry_run {
+        let mut fork = ledger.fork();
+        rejects.headers = read_file(&cli.path, |record, tx| {
+            if let Err(e) = tx.and_then(|tx| Ok(fork.process_tx(tx)?)) {
+                rejects.add(record, e)
+            }
+        })?;
+        if let Some(path) = &cli.rejects {
+            rejects.write(path)?;
+        }
+        return write_diff(fork.diff()?);
+    }
+    let ledger = process_file(&cli.path, ledger, cli.threads, &mut rejects)?;
+    if let Some(path) = &cli.rejects {
+        rejects.write(path)?;
+    }
+    if let Some(path) = &cli.state_out {
+        let mut wtr = io::BufWriter::new(File::create(path)?);
+        ledger.snapshot(&mut wtr)?;
+        wtr.flush()?;
+    }
 
-    let accounts = ledger.get_accounts();
+    let accounts = ledger.get_accounts().collect::<Result<Vec<_>, _>>()?;
     let mut wtr = csv::Writer::from_writer(io::stdout());
     match cli.sort {
         true => {
@@ -40,13 +201,17 @@ fn main() -> Result<(), Error> {
                 btree.insert(account.id, account);
             }
             for account in btree.values() {
-                wtr.serialize(account)?;
+                for row in account.rows() {
+                    wtr.serialize(row)?;
+                }
             }
             wtr.flush()?;
         }
         false => {
             for account in accounts {
-                wtr.serialize(account)?;
+                for row in account.rows() {
+                    wtr.serialize(row)?;
+                }
             }
             wtr.flush()?;
         }
@@ -55,27 +220,106 @@ fn main() -> Result<(), Error> {
     Ok(())
 }
 
-fn process_file(path: &str) -> Result<Ledger, Error> {
+fn write_diff(changes: Vec<RowChange>) -> Result<(), Box<dyn error::Error>> {
+    let mut wtr = csv::Writer::from_writer(io::stdout());
+    for change in changes {
+        if let Some(row) = change.before {
+            wtr.serialize(DiffRow::new('-', row))?;
+        }
+        if let Some(row) = change.after {
+            wtr.serialize(DiffRow::new('+', row))?;
+        }
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+fn process_file(
+    path: &str,
+    mut ledger: Ledger,
+    threads: NonZeroUsize,
+    rejects: &mut Rejects,
+) -> Result<Ledger, Error> {
+    if threads.get() == 1 {
+        let headers = read_file(path, |record, tx| {
+            if let Err(e) = tx.and_then(|tx| Ok(ledger.process_tx(tx)?)) {
+                rejects.add(record, e)
+            }
+        })?;
+        rejects.headers = headers;
+        return Ok(ledger);
+    }
+
+    // Failures of the parallel ledger are numbered in the order the transactions were queued
+    let mut records = Vec::new();
+    let mut ledger = ParallelLedger::new(ledger, threads);
+    let headers = read_file(path, |record, tx| match tx {
+        Ok(tx) => {
+            if rejects.enabled {
+                records.push(record.clone());
+            }
+            ledger.process_tx(tx)
+        }
+        Err(e) => rejects.add(record, e),
+    })?;
+    rejects.headers = headers;
+    let (ledger, failures) = ledger.finish();
+    for (index, e) in failures {
+        match records.get(index) {
+            Some(record) => rejects.add(record, e.into()),
+            None => warn!("{e}"),
+        }
+    }
+    Ok(ledger)
+}
+
+fn read_clients(path: &str, ledger: &mut Ledger) -> Result<(), Error> {
+    let mut rdr = csv::ReaderBuilder::new().trim(Trim::All).from_path(path)?;
+
+    for result in rdr.deserialize::<ClientRecord>() {
+        match result {
+            Ok(record) => {
+                let limit = record.credit_limit;
+                if let Err(e) = ledger.set_credit_limit(record.client, record.currency, limit) {
+                    warn!("{e}")
+                }
+            }
+            Err(e) => warn!("{e}"),
+        }
+    }
+    Ok(())
+}
+
+/// Reads the input file, passing every record with its transaction or the reason it could not
+/// be read, and returns the headers
+fn read_file<F>(path: &str, mut process: F) -> Result<StringRecord, Error>
+where
+    F: FnMut(&StringRecord, Result<Transaction, RecordFailure>),
+{
     let mut rdr = csv::ReaderBuilder::new()
         .trim(Trim::All)
         .flexible(true)
-        .from_path(&path)?;
+        .from_path(path)?;
+    let headers = rdr.headers()?.clone();
 
-    let mut ledger = Ledger::new();
-
-    while let Some(result) = rdr.deserialize::<TransactionRecord>().next() {
+    for result in rdr.records() {
         match result {
-            Ok(record) => match record.try_into() {
-                Ok(tx) => {
+            Ok(record) => {
+                let tx = record
+                    .deserialize::<TransactionRecord>(Some(&headers))
+                    .map_err(RecordFailure::from)
+                    .and_then(|record| Ok(Transaction::try_from(record)?));
+                if let Ok(tx) = &tx {
                     debug!("Attempting to process {tx:#?}");
-                    if let Err(e) = ledger.process_tx(tx) {
-                        warn!("{e}")
-                    }
                 }
-                Err(e) => warn!("{e}"),
-            },
-            Err(e) => warn!("{e}"),
+                process(&record, tx);
+            }
+            Err(e) => {
+                let mut record = StringRecord::new();
+                record.set_position(e.position().cloned());
+                process(&record, Err(e.into()));
+            }
         }
     }
-    Ok(ledger)
+    Ok(headers)
 }
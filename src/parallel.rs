@@ -0,0 +1,651 @@
+use crate::{
+    error::Error,
+    storage::{MemoryStorage, Storage},
+    ClientId, Event, Ledger, Transaction, TxId,
+};
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet},
+    hash::{Hash, Hasher},
+    mem,
+    num::NonZeroUsize,
+    sync::mpsc::{self, Receiver, SyncSender},
+    thread::{self, JoinHandle},
+};
+use tracing::debug;
+
+/// Number of transactions that can be queued for a shard before [`ParallelLedger::process_tx`]
+/// blocks
+const QUEUE_SIZE: usize = 1024;
+
+type Failures = Vec<(usize, Error)>;
+
+enum Job {
+    /// Processes a transaction
+    Process(usize, Transaction),
+    /// Creates the account of a rejected transaction, if it does not exist yet
+    Open(usize, ClientId),
+}
+
+/// Processes transactions on worker threads, each owning the accounts of a subset of clients
+///
+/// Transactions are routed to a shard on [`ClientId`], so the transactions of a client are
+/// processed in order. Duplicate transaction IDs are rejected before routing, which gives the
+/// same final balances as processing all transactions with [`Ledger::process_tx`]. When failed
+/// transactions do not use up their ID, a transaction that reuses the ID of a queued
+/// transaction waits until the queued transaction is processed.
+///
+/// A transfer changes the accounts of two clients, which can be in different shards. Transfers,
+/// and disputes, resolves and chargebacks of transfers, wait for all queued transactions and
+/// are processed on the merged shards before the shards are split again. With an expiry of
+/// disputes, the transactions of a client with an open dispute of a transfer are processed on
+/// the merged shards too, as they can close the dispute on both accounts.
+///
+/// The observers of the ledger are notified from the worker threads, so the events of different
+/// shards can arrive in any order. A deposit or withdrawal with a used ID is checked when the
+/// shards are merged, its [`Event::Rejected`] is sent then.
+///
+/// # Example
+/// ```rust
+/// use ledger_rs::{Ledger, ParallelLedger, Transaction};
+/// use rust_decimal::Decimal;
+/// use std::num::NonZeroUsize;
+///
+/// let mut ledger = ParallelLedger::new(Ledger::new(), NonZeroUsize::new(4).unwrap());
+/// for id in 0..100u64 {
+///     ledger.process_tx(Transaction::Deposit {
+///         id: id.into(),
+///         client: (id % 10).into(),
+///         amount: Decimal::new(2, 0),
+///         currency: None,
+///         timestamp: None,
+///     });
+/// }
+/// let (ledger, failures) = ledger.finish();
+///
+/// assert!(failures.is_empty());
+/// assert_eq!(ledger.get_accounts().count(), 10);
+/// ```
+pub struct ParallelLedger {
+    threads: NonZeroUsize,
+    senders: Vec<SyncSender<Job>>,
+    workers: Vec<JoinHandle<(MemoryStorage, Failures)>>,
+    tx_ids: HashSet<TxId>,
+    /// IDs of queued deposits and withdrawals that only use up their ID when they succeed
+    pending: HashSet<TxId>,
+    /// Outcomes of the pending deposits and withdrawals
+    outcomes: Receiver<(TxId, bool)>,
+    /// Deposits and withdrawals with a used ID, checked against the merged shards
+    replays: Vec<(usize, Transaction)>,
+    /// IDs of the transfers, their disputes are processed on the merged shards
+    transfers: HashSet<TxId>,
+    /// Clients with an open dispute of a transfer
+    disputed: HashSet<ClientId>,
+    failures: Failures,
+    count: usize,
+    template: Ledger,
+}
+
+impl ParallelLedger {
+    /// Spreads the accounts of the ledger over the given number of worker threads
+    pub fn new(ledger: Ledger, threads: NonZeroUsize) -> Self {
+        let transfers = ledger
+            .storage()
+            .transactions
+            .iter()
+            .filter(|(_, record)| matches!(record.transaction, Transaction::Transfer { .. }))
+            .map(|(id, _)| *id)
+            .collect::<HashSet<_>>();
+        let disputed = ledger
+            .storage()
+            .accounts()
+            .filter_map(Result::ok)
+            .filter(|account| account.open_disputes().any(|id| transfers.contains(&id)))
+            .map(|account| account.id)
+            .collect();
+        let (_, outcomes) = mpsc::channel();
+        let mut parallel = Self {
+            threads,
+            senders: Vec::new(),
+            workers: Vec::new(),
+            tx_ids: HashSet::new(),
+            pending: HashSet::new(),
+            outcomes,
+            replays: Vec::new(),
+            transfers,
+            disputed,
+            failures: Vec::new(),
+            count: 0,
+            template: ledger.derive(MemoryStorage::default()),
+        };
+        parallel.spawn(ledger);
+        parallel
+    }
+
+    /// Splits the ledger into shards and starts a worker thread for each shard
+    fn spawn(&mut self, ledger: Ledger) {
+        let threads = self.threads.get();
+        let mut storage = ledger.into_storage();
+        self.tx_ids = mem::take(&mut storage.tx_ids);
+        let shards = storage.split(threads, |client| shard(client, threads));
+        let (outcome, outcomes) = mpsc::channel();
+        self.outcomes = outcomes;
+        let config = self.template.config();
+        let report = config.unique_tx_ids() && !config.reserve_failed_tx_ids();
+
+        let (senders, workers) = shards
+            .into_iter()
+            .enumerate()
+            .map(|(n, storage)| {
+                let (sender, receiver) = mpsc::sync_channel::<Job>(QUEUE_SIZE);
+                let mut ledger = self.template.derive(storage);
+                let outcome = outcome.clone();
+                let worker = thread::spawn(move || {
+                    debug!("Starting shard {n}");
+                    let mut failures = Vec::new();
+                    for job in receiver {
+                        let result = match job {
+                            Job::Process(index, tx) => {
+                                let id = tx.id();
+                                let reserves_id = tx.reserves_id();
+                                let result = ledger.process_tx(tx);
+                                if report && reserves_id {
+                                    // The router only stops receiving when it is dropped
+                                    let _ = outcome.send((id, result.is_ok()));
+                                }
+                                result.map(|_| ()).map_err(|e| (index, e))
+                            }
+                            Job::Open(index, client) => {
+                                ledger.open_account(client).map_err(|e| (index, e))
+                            }
+                        };
+                        if let Err(failure) = result {
+                            failures.push(failure);
+                        }
+                    }
+                    (ledger.into_storage(), failures)
+                });
+                (sender, worker)
+            })
+            .unzip();
+        self.senders = senders;
+        self.workers = workers;
+    }
+
+    /// Waits for all queued transactions to be processed and merges the shards
+    ///
+    /// # Panics
+    /// Panics if a worker thread panicked
+    fn join(&mut self) -> Ledger {
+        self.senders.clear();
+        let mut storage = MemoryStorage::default();
+        for worker in mem::take(&mut self.workers) {
+            match worker.join() {
+                Ok((shard, failures)) => {
+                    storage.merge(shard);
+                    self.failures.extend(failures);
+                }
+                Err(panic) => std::panic::resume_unwind(panic),
+            }
+        }
+        // The shards hold the IDs of the pending transactions that succeeded
+        storage.tx_ids.extend(mem::take(&mut self.tx_ids));
+        self.pending.clear();
+        // The processed transaction with the ID of a replay can be in any shard
+        let ledger = self.template.derive(storage);
+        for (index, tx) in mem::take(&mut self.replays) {
+            if let Err(e) = ledger.replay(tx.clone()) {
+                ledger.notify(&Event::Rejected { tx: &tx, error: &e });
+                self.failures.push((index, e));
+            }
+        }
+        ledger
+    }
+
+    /// Queues a transaction for processing, blocks while the queue of its shard is full
+    ///
+    /// Transactions are numbered in the order they are queued, starting at zero. Failed
+    /// transactions are reported with their number by [`ParallelLedger::finish`].
+    pub fn process_tx(&mut self, tx: Transaction) {
+        let index = self.count;
+        self.count += 1;
+
+        match tx {
+            Transaction::Transfer { id, .. } => {
+                self.transfers.insert(id);
+                return self.process_merged(index, tx);
+            }
+            Transaction::Dispute { id, .. }
+            | Transaction::Resolve { id, .. }
+            | Transaction::Chargeback { id, .. }
+                if self.transfers.contains(&id) =>
+            {
+                return self.process_merged(index, tx);
+            }
+            // An expired dispute of a transfer is closed on both accounts
+            _ if self.template.config().disputes().expiry.is_some()
+                && self.disputed.contains(&tx.client()) =>
+            {
+                return self.process_merged(index, tx);
+            }
+            _ => {}
+        }
+        if tx.reserves_id() && self.template.config().unique_tx_ids() && !self.reserve(tx.id()) {
+            // The account is still created, like it would be by `Ledger::process_tx`
+            self.send(tx.client(), Job::Open(index, tx.client()));
+            self.replays.push((index, tx));
+            return;
+        }
+        self.send(tx.client(), Job::Process(index, tx));
+    }
+
+    /// Processes a transaction on the merged shards
+    fn process_merged(&mut self, index: usize, tx: Transaction) {
+        let client = tx.client();
+        let mut ledger = self.join();
+        if let Err(e) = ledger.process_tx(tx) {
+            self.failures.push((index, e));
+        }
+        let disputed = ledger
+            .account(client)
+            .ok()
+            .flatten()
+            .is_some_and(|account| {
+                account
+                    .open_disputes()
+                    .any(|id| self.transfers.contains(&id))
+            });
+        match disputed {
+            true => self.disputed.insert(client),
+            false => self.disputed.remove(&client),
+        };
+        self.spawn(ledger);
+    }
+
+    /// Reserves the ID of a deposit or withdrawal, returns `false` if the ID is used
+    ///
+    /// When failed transactions do not use up their ID, the ID is pending until the outcome of
+    /// the transaction is known. Reserving a pending ID waits for that outcome.
+    fn reserve(&mut self, id: TxId) -> bool {
+        if self.template.config().reserve_failed_tx_ids() {
+            return self.tx_ids.insert(id);
+        }
+        while let Ok((id, success)) = self.outcomes.try_recv() {
+            self.settle(id, success);
+        }
+        while self.pending.contains(&id) {
+            match self.outcomes.recv() {
+                Ok((id, success)) => self.settle(id, success),
+                // All workers stopped, the panic is raised by `ParallelLedger::finish`
+                Err(..) => break,
+            }
+        }
+        if self.tx_ids.contains(&id) {
+            return false;
+        }
+        self.pending.insert(id)
+    }
+
+    fn settle(&mut self, id: TxId, success: bool) {
+        self.pending.remove(&id);
+        if success {
+            self.tx_ids.insert(id);
+        }
+    }
+
+    fn send(&self, client: ClientId, job: Job) {
+        let n = shard(client, self.senders.len());
+        // Workers only stop when the senders are dropped, a failed send means a worker panicked
+        if self.senders[n].send(job).is_err() {
+            debug!("Shard {n} stopped unexpectedly");
+        }
+    }
+
+    /// Waits for all queued transactions to be processed and merges the shards into a single
+    /// ledger
+    ///
+    /// Returns the ledger and the failed transactions, with their numbers in ascending order.
+    ///
+    /// # Panics
+    /// Panics if a worker thread panicked
+    pub fn finish(mut self) -> (Ledger, Vec<(usize, Error)>) {
+        let ledger = self.join();
+        let mut failures = self.failures;
+        failures.sort_by_key(|(index, _)| *index);
+        (ledger, failures)
+    }
+}
+
+fn shard(client: ClientId, shards: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    client.hash(&mut hasher);
+    (hasher.finish() % shards as u64) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParallelLedger;
+    use crate::{
+        error::Error, Account, AccountRow, DisputeExpiry, DisputePolicy, Event, ExpiryAction, Id,
+        Ledger, LedgerConfig, Period, Transaction,
+    };
+    use rust_decimal::Decimal;
+    use std::{
+        num::NonZeroUsize,
+        sync::{Arc, Mutex},
+    };
+
+    fn transactions() -> Vec<Transaction> {
+        let mut txs = Vec::new();
+        for id in 0..1000u64 {
+            let client = id % 37;
+            let amount = Decimal::new(id as i64 % 7, 1);
+            txs.push(match id % 5 {
+                0 | 1 => Transaction::Deposit {
+                    id: id.into(),
+                    client: client.into(),
+                    amount,
+                    currency: None,
+                    timestamp: None,
+                },
+                2 => Transaction::Withdrawal {
+                    id: id.into(),
+                    client: client.into(),
+                    amount,
+                    currency: None,
+                    timestamp: None,
+                },
+                3 => Transaction::Dispute {
+                    id: (id - 3).into(),
+                    client: client.into(),
+                    amount: None,
+                    timestamp: None,
+                },
+                // Reuses the ID of a deposit of another client
+                _ => Transaction::Deposit {
+                    id: (id - 4).into(),
+                    client: (client + 1).into(),
+                    amount,
+                    currency: None,
+                    timestamp: None,
+                },
+            });
+        }
+        txs
+    }
+
+    fn rows(ledger: &Ledger) -> Vec<AccountRow> {
+        let mut rows = ledger
+            .get_accounts()
+            .flat_map(|account| account.unwrap().rows().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        rows.sort_by_key(|row| row.client);
+        rows
+    }
+
+    #[test]
+    fn same_as_sequential() {
+        // Setup
+        let mut sequential = Ledger::new();
+        let mut expected = Vec::new();
+        for (index, tx) in transactions().into_iter().enumerate() {
+            if let Err(e) = sequential.process_tx(tx) {
+                expected.push((index, e.to_string()));
+            }
+        }
+        let mut ledger = ParallelLedger::new(Ledger::new(), NonZeroUsize::new(4).unwrap());
+
+        // Act
+        for tx in transactions() {
+            ledger.process_tx(tx);
+        }
+        let (ledger, failures) = ledger.finish();
+
+        // Assert
+        let failures = failures
+            .into_iter()
+            .map(|(index, e)| (index, e.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(failures, expected);
+        assert_eq!(rows(&ledger), rows(&sequential));
+    }
+
+    #[test]
+    fn same_as_sequential_unreserved() {
+        // Setup
+        let config = LedgerConfig::builder().reserve_failed_tx_ids(false).build();
+        let mut sequential = Ledger::with_config(config.clone());
+        let mut expected = Vec::new();
+        for (index, tx) in transactions().into_iter().enumerate() {
+            if let Err(e) = sequential.process_tx(tx) {
+                expected.push((index, e.to_string()));
+            }
+        }
+        let ledger = Ledger::with_config(config);
+        let mut ledger = ParallelLedger::new(ledger, NonZeroUsize::new(4).unwrap());
+
+        // Act
+        for tx in transactions() {
+            ledger.process_tx(tx);
+        }
+        let (ledger, failures) = ledger.finish();
+
+        // Assert
+        let failures = failures
+            .into_iter()
+            .map(|(index, e)| (index, e.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(failures, expected);
+        assert_eq!(rows(&ledger), rows(&sequential));
+        let mut tx_ids = ledger.storage().tx_ids.iter().collect::<Vec<_>>();
+        let mut expected = sequential.storage().tx_ids.iter().collect::<Vec<_>>();
+        tx_ids.sort();
+        expected.sort();
+        assert_eq!(tx_ids, expected);
+    }
+
+    #[test]
+    fn transfers() {
+        // Setup
+        let mut txs = transactions();
+        for id in (0..1000u64).step_by(50) {
+            let from = id % 37;
+            txs.insert(
+                id as usize,
+                Transaction::Transfer {
+                    id: (2000 + id).into(),
+                    from: from.into(),
+                    to: (from + 3).into(),
+                    amount: Decimal::new(5, 1),
+                    currency: None,
+                    timestamp: None,
+                },
+            );
+            txs.push(Transaction::Dispute {
+                id: (2000 + id).into(),
+                client: from.into(),
+                amount: None,
+                timestamp: None,
+            });
+        }
+        let mut sequential = Ledger::new();
+        let mut expected = Vec::new();
+        for (index, tx) in txs.clone().into_iter().enumerate() {
+            if let Err(e) = sequential.process_tx(tx) {
+                expected.push((index, e.to_string()));
+            }
+        }
+        let mut ledger = ParallelLedger::new(Ledger::new(), NonZeroUsize::new(4).unwrap());
+
+        // Act
+        for tx in txs {
+            ledger.process_tx(tx);
+        }
+        let (ledger, failures) = ledger.finish();
+
+        // Assert
+        let failures = failures
+            .into_iter()
+            .map(|(index, e)| (index, e.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(failures, expected);
+        assert_eq!(rows(&ledger), rows(&sequential));
+    }
+
+    #[test]
+    fn transfer_dispute_expiry() {
+        // Setup
+        let config = LedgerConfig::builder()
+            .disputes(DisputePolicy {
+                window: Period::Unlimited,
+                expiry: Some(DisputeExpiry {
+                    after: Period::Time(10),
+                    action: ExpiryAction::Chargeback,
+                }),
+            })
+            .build();
+        let deposit = |id, timestamp| Transaction::Deposit {
+            id,
+            client: 1.into(),
+            amount: Decimal::new(100, 0),
+            currency: None,
+            timestamp: Some(timestamp),
+        };
+
+        for to in (2..=7).map(Id::from) {
+            let txs = [
+                deposit(1.into(), 0),
+                Transaction::Transfer {
+                    id: 2.into(),
+                    from: 1.into(),
+                    to,
+                    amount: Decimal::new(40, 0),
+                    currency: None,
+                    timestamp: Some(1),
+                },
+                Transaction::Dispute {
+                    id: 2.into(),
+                    client: 1.into(),
+                    amount: None,
+                    timestamp: Some(2),
+                },
+                deposit(3.into(), 20),
+            ];
+            let mut sequential = Ledger::with_config(config.clone());
+            for tx in txs.clone() {
+                let _ = sequential.process_tx(tx);
+            }
+            let ledger = Ledger::with_config(config.clone());
+            let mut ledger = ParallelLedger::new(ledger, NonZeroUsize::new(4).unwrap());
+
+            // Act
+            for tx in txs {
+                ledger.process_tx(tx);
+            }
+            let (ledger, _) = ledger.finish();
+
+            // Assert
+            assert_eq!(rows(&ledger), rows(&sequential), "transfer to {to}");
+        }
+    }
+
+    #[test]
+    fn duplicate_tx_id() {
+        // Setup
+        let id = Id::from(1);
+        let amount = Decimal::new(2, 0);
+        let mut ledger = ParallelLedger::new(Ledger::new(), NonZeroUsize::new(2).unwrap());
+        ledger.process_tx(Transaction::Deposit {
+            id,
+            client: 1.into(),
+            amount,
+            currency: None,
+            timestamp: None,
+        });
+
+        // Act
+        ledger.process_tx(Transaction::Deposit {
+            id,
+            client: 2.into(),
+            amount,
+            currency: None,
+            timestamp: None,
+        });
+        let (ledger, failures) = ledger.finish();
+
+        // Assert
+        assert_eq!(failures.len(), 1);
+        assert!(matches!(failures[0], (1, Error::ConflictingTxId { .. })));
+        assert_eq!(ledger.get_accounts().count(), 2);
+    }
+
+    #[test]
+    fn observers() {
+        // Setup
+        let rejected = Arc::new(Mutex::new(Vec::new()));
+        let mut sequential = Ledger::new();
+        let observed = rejected.clone();
+        sequential.add_observer(move |event: &Event| {
+            if let Event::Rejected { tx, .. } = event {
+                observed.lock().unwrap().push(tx.id());
+            }
+        });
+        let mut ledger = ParallelLedger::new(
+            sequential.derive(Default::default()),
+            NonZeroUsize::new(2).unwrap(),
+        );
+
+        // Act
+        for tx in transactions() {
+            ledger.process_tx(tx);
+        }
+        let (_, failures) = ledger.finish();
+        let mut parallel = rejected.lock().unwrap().drain(..).collect::<Vec<_>>();
+
+        // Assert
+        for tx in transactions() {
+            let _ = sequential.process_tx(tx);
+        }
+        let mut sequential = rejected.lock().unwrap().clone();
+        parallel.sort();
+        sequential.sort();
+        assert_eq!(parallel.len(), failures.len());
+        assert_eq!(parallel, sequential);
+    }
+
+    #[test]
+    fn validators() {
+        // Setup
+        let mut sequential = Ledger::new();
+        sequential.add_validator(|tx: &Transaction, _: &Account| match tx.client() == 13 {
+            true => Err("client is blocked".into()),
+            false => Ok(()),
+        });
+        let mut ledger = ParallelLedger::new(
+            sequential.derive(Default::default()),
+            NonZeroUsize::new(4).unwrap(),
+        );
+
+        // Act
+        for tx in transactions() {
+            ledger.process_tx(tx);
+        }
+        let (mut ledger, failures) = ledger.finish();
+
+        // Assert
+        for tx in transactions() {
+            let _ = sequential.process_tx(tx);
+        }
+        assert!(failures
+            .iter()
+            .any(|(_, e)| matches!(e, Error::Rejected(..))));
+        assert_eq!(rows(&ledger), rows(&sequential));
+        let result = ledger.process_tx(Transaction::Deposit {
+            id: 5000.into(),
+            client: 13.into(),
+            amount: Decimal::ONE,
+            currency: None,
+            timestamp: None,
+        });
+        assert!(matches!(result, Err(Error::Rejected(id, ..)) if id == 5000));
+    }
+}
@@ -0,0 +1,74 @@
+use crate::{error::Error, storage::Storage, Account, HistoryEntry, TxId, TxRecord};
+use serde::{
+    ser::{self, SerializeSeq, SerializeStruct},
+    Deserialize, Serialize, Serializer,
+};
+use std::{
+    cell::RefCell,
+    io::{Read, Write},
+};
+
+/// Owned snapshot, as read by [`read`]
+#[derive(Deserialize)]
+struct Snapshot {
+    accounts: Vec<Account>,
+    transactions: Vec<(TxId, TxRecord)>,
+    tx_ids: Vec<TxId>,
+    #[serde(default)]
+    history: Vec<HistoryEntry>,
+}
+
+/// Serializes the items of a storage iterator as a sequence without collecting them first
+struct Seq<I>(RefCell<Option<I>>);
+
+impl<I> Seq<I> {
+    fn new(iter: I) -> Self {
+        Self(RefCell::new(Some(iter)))
+    }
+}
+
+impl<T: Serialize, I: Iterator<Item = Result<T, Error>>> Serialize for Seq<I> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let iter = self
+            .0
+            .borrow_mut()
+            .take()
+            .ok_or_else(|| ser::Error::custom("sequence already serialized"))?;
+        let mut seq = serializer.serialize_seq(None)?;
+        for item in iter {
+            seq.serialize_element(&item.map_err(ser::Error::custom)?)?;
+        }
+        seq.end()
+    }
+}
+
+/// Writes all accounts, processed transactions, used transaction IDs and account histories in
+/// the storage as JSON
+pub(crate) fn write<S: Storage + ?Sized, W: Write>(storage: &S, writer: W) -> Result<(), Error> {
+    let mut serializer = serde_json::Serializer::new(writer);
+    let mut snapshot = serializer.serialize_struct("Snapshot", 4)?;
+    snapshot.serialize_field("accounts", &Seq::new(storage.accounts()))?;
+    snapshot.serialize_field("transactions", &Seq::new(storage.transactions()))?;
+    snapshot.serialize_field("tx_ids", &Seq::new(storage.tx_ids()))?;
+    snapshot.serialize_field("history", &Seq::new(storage.histories()))?;
+    SerializeStruct::end(snapshot)?;
+    Ok(())
+}
+
+/// Reads a snapshot written by [`write`] into the storage
+pub(crate) fn read<S: Storage + ?Sized, R: Read>(storage: &mut S, reader: R) -> Result<(), Error> {
+    let snapshot: Snapshot = serde_json::from_reader(reader)?;
+    for account in snapshot.accounts {
+        storage.put_account(account)?;
+    }
+    for (id, record) in snapshot.transactions {
+        storage.put_transaction(id, record)?;
+    }
+    for id in snapshot.tx_ids {
+        storage.insert_tx_id(id)?;
+    }
+    for entry in snapshot.history {
+        storage.put_history(entry)?;
+    }
+    Ok(())
+}
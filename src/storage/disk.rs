@@ -0,0 +1,370 @@
+use super::{MemoryStorage, Storage, StorageIter};
+use crate::{error::Error, Account, ClientId, HistoryEntry, TxId, TxRecord};
+use serde::{de::DeserializeOwned, Serialize};
+use sled::{transaction::TransactionResult, Transactional};
+use std::{collections::BTreeMap, path::Path};
+
+/// Disk-backed storage in an embedded [`sled`] database
+///
+/// Keys and values are stored as JSON, except for the keys of the account histories. Those are
+/// the JSON client ID, a zero byte and the big-endian sequence number, so that a history is
+/// stored in order. JSON never contains a zero byte, so the prefix of one client is never the
+/// start of the prefix of another.
+pub struct SledStorage {
+    accounts: sled::Tree,
+    transactions: sled::Tree,
+    tx_ids: sled::Tree,
+    history: sled::Tree,
+}
+
+impl SledStorage {
+    /// Opens or creates a database at the given path
+    ///
+    /// # Errors
+    /// Returns [`Error::Storage`] if the database cannot be opened
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_db(&sled::open(path).map_err(storage_error)?)
+    }
+
+    /// Creates a database that is removed when it is dropped
+    ///
+    /// # Errors
+    /// Returns [`Error::Storage`] if the database cannot be created
+    pub fn temporary() -> Result<Self, Error> {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .map_err(storage_error)?;
+        Self::from_db(&db)
+    }
+
+    fn from_db(db: &sled::Db) -> Result<Self, Error> {
+        Ok(Self {
+            accounts: db.open_tree("accounts").map_err(storage_error)?,
+            transactions: db.open_tree("transactions").map_err(storage_error)?,
+            tx_ids: db.open_tree("tx_ids").map_err(storage_error)?,
+            history: db.open_tree("history").map_err(storage_error)?,
+        })
+    }
+
+    /// Flushes all pending writes to disk
+    ///
+    /// # Errors
+    /// Returns [`Error::Storage`] if flushing fails
+    pub fn flush(&self) -> Result<(), Error> {
+        for tree in [
+            &self.accounts,
+            &self.transactions,
+            &self.tx_ids,
+            &self.history,
+        ] {
+            tree.flush().map_err(storage_error)?;
+        }
+        Ok(())
+    }
+}
+
+impl Storage for SledStorage {
+    fn account(&self, client: ClientId) -> Result<Option<Account>, Error> {
+        get(&self.accounts, &client)
+    }
+
+    fn put_account(&mut self, account: Account) -> Result<(), Error> {
+        put(&self.accounts, &account.id, &account)
+    }
+
+    fn accounts(&self) -> StorageIter<'_, Account> {
+        Box::new(iter::<ClientId, Account>(&self.accounts).map(|r| r.map(|(_, a)| a)))
+    }
+
+    fn transaction(&self, id: TxId) -> Result<Option<TxRecord>, Error> {
+        get(&self.transactions, &id)
+    }
+
+    fn put_transaction(&mut self, id: TxId, record: TxRecord) -> Result<(), Error> {
+        put(&self.transactions, &id, &record)
+    }
+
+    fn transactions(&self) -> StorageIter<'_, (TxId, TxRecord)> {
+        Box::new(iter(&self.transactions))
+    }
+
+    fn contains_tx_id(&self, id: TxId) -> Result<bool, Error> {
+        self.tx_ids
+            .contains_key(encode(&id)?)
+            .map_err(storage_error)
+    }
+
+    fn insert_tx_id(&mut self, id: TxId) -> Result<bool, Error> {
+        let key = encode(&id)?;
+        let previous = self.tx_ids.insert(key, Vec::new()).map_err(storage_error)?;
+        Ok(previous.is_none())
+    }
+
+    fn tx_ids(&self) -> StorageIter<'_, TxId> {
+        Box::new(self.tx_ids.iter().keys().map(|key| {
+            let key = key.map_err(storage_error)?;
+            decode(&key)
+        }))
+    }
+
+    fn history(&self, client: ClientId) -> StorageIter<'_, HistoryEntry> {
+        let prefix = match history_prefix(client) {
+            Ok(prefix) => prefix,
+            Err(e) => return Box::new(std::iter::once(Err(e))),
+        };
+        Box::new(self.history.scan_prefix(prefix).values().map(|value| {
+            let value = value.map_err(storage_error)?;
+            decode(&value)
+        }))
+    }
+
+    fn put_history(&mut self, entry: HistoryEntry) -> Result<(), Error> {
+        let mut key = history_prefix(entry.client())?;
+        key.extend(entry.sequence.to_be_bytes());
+        self.history
+            .insert(key, encode(&entry)?)
+            .map_err(storage_error)?;
+        Ok(())
+    }
+
+    fn histories(&self) -> StorageIter<'_, HistoryEntry> {
+        Box::new(self.history.iter().values().map(|value| {
+            let value = value.map_err(storage_error)?;
+            decode(&value)
+        }))
+    }
+
+    fn commit(&mut self, changes: MemoryStorage) -> Result<(), Error> {
+        // Everything is encoded up front, a transaction may run more than once on conflicts
+        let accounts = changes
+            .accounts
+            .iter()
+            .map(|(client, account)| Ok((encode(client)?, encode(account)?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let transactions = changes
+            .transactions
+            .iter()
+            .map(|(id, record)| Ok((encode(id)?, encode(record)?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let tx_ids = changes
+            .tx_ids
+            .iter()
+            .map(encode)
+            .collect::<Result<Vec<_>, Error>>()?;
+        let history = changes
+            .history
+            .into_values()
+            .flat_map(BTreeMap::into_values)
+            .map(|entry| {
+                let mut key = history_prefix(entry.client())?;
+                key.extend(entry.sequence.to_be_bytes());
+                Ok((key, encode(&entry)?))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let trees = (
+            &self.accounts,
+            &self.transactions,
+            &self.tx_ids,
+            &self.history,
+        );
+        let result: TransactionResult<(), Error> = trees.transaction(
+            |(accounts_tree, transactions_tree, tx_ids_tree, history_tree)| {
+                for (key, value) in &accounts {
+                    accounts_tree.insert(key.as_slice(), value.as_slice())?;
+                }
+                for (key, value) in &transactions {
+                    transactions_tree.insert(key.as_slice(), value.as_slice())?;
+                }
+                for key in &tx_ids {
+                    tx_ids_tree.insert(key.as_slice(), Vec::new())?;
+                }
+                for (key, value) in &history {
+                    history_tree.insert(key.as_slice(), value.as_slice())?;
+                }
+                Ok(())
+            },
+        );
+        result.map_err(storage_error)
+    }
+}
+
+fn get<K: Serialize, V: DeserializeOwned>(tree: &sled::Tree, key: &K) -> Result<Option<V>, Error> {
+    match tree.get(encode(key)?).map_err(storage_error)? {
+        Some(value) => Ok(Some(decode(&value)?)),
+        None => Ok(None),
+    }
+}
+
+fn put<K: Serialize, V: Serialize>(tree: &sled::Tree, key: &K, value: &V) -> Result<(), Error> {
+    tree.insert(encode(key)?, encode(value)?)
+        .map_err(storage_error)?;
+    Ok(())
+}
+
+fn iter<K: DeserializeOwned, V: DeserializeOwned>(
+    tree: &sled::Tree,
+) -> impl Iterator<Item = Result<(K, V), Error>> {
+    tree.iter().map(|entry| {
+        let (key, value) = entry.map_err(storage_error)?;
+        Ok((decode(&key)?, decode(&value)?))
+    })
+}
+
+/// Gets the start of the history keys of a client
+fn history_prefix(client: ClientId) -> Result<Vec<u8>, Error> {
+    let mut prefix = encode(&client)?;
+    prefix.push(0);
+    Ok(prefix)
+}
+
+fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+    serde_json::to_vec(value).map_err(storage_error)
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Error> {
+    serde_json::from_slice(bytes).map_err(storage_error)
+}
+
+fn storage_error<E: std::error::Error>(e: E) -> Error {
+    Error::Storage(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SledStorage;
+    use crate::{error::Error, storage::Storage, ClientId, Id, Ledger, Transaction};
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn process_tx() {
+        // Setup
+        let id = Id::from(1);
+        let client = Id::from(1);
+        let amount = Decimal::new(2, 0);
+        let mut ledger = Ledger::with_storage(SledStorage::temporary().unwrap());
+
+        assert!(ledger
+            .process_tx(Transaction::Deposit {
+                id,
+                client,
+                amount,
+                currency: None,
+                timestamp: None,
+            })
+            .is_ok());
+        let tx = Transaction::Dispute {
+            id,
+            client,
+            amount: None,
+            timestamp: None,
+        };
+
+        // Act
+        let result = ledger.process_tx(tx);
+
+        // Assert
+        assert!(result.is_ok());
+        let accounts = ledger
+            .get_accounts()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(accounts.len(), 1);
+        let rows = accounts[0].rows().collect::<Vec<_>>();
+        assert_eq!(rows[0].available, Decimal::ZERO);
+        assert_eq!(rows[0].held, amount);
+    }
+
+    #[test]
+    fn duplicate_tx_id() {
+        // Setup
+        let id = Id::from(1);
+        let client = Id::from(1);
+        let amount = Decimal::new(2, 0);
+        let mut ledger = Ledger::with_storage(SledStorage::temporary().unwrap());
+
+        assert!(ledger
+            .process_tx(Transaction::Deposit {
+                id,
+                client,
+                amount,
+                currency: None,
+                timestamp: None,
+            })
+            .is_ok());
+        let tx = Transaction::Withdrawal {
+            id,
+            client,
+            amount,
+            currency: None,
+            timestamp: None,
+        };
+
+        // Act
+        let result = ledger.process_tx(tx);
+
+        // Assert
+        assert!(matches!(result, Err(Error::ConflictingTxId { .. })));
+    }
+
+    #[test]
+    fn ids() {
+        // Setup
+        let id = Id::from_uuid(u128::MAX);
+        let client = "acct-1".parse::<ClientId>().unwrap();
+        let other = "acct-17".parse::<ClientId>().unwrap();
+        let amount = Decimal::new(2, 0);
+        let mut ledger = Ledger::with_storage(SledStorage::temporary().unwrap());
+        let txs = [
+            Transaction::Deposit {
+                id,
+                client,
+                amount,
+                currency: None,
+                timestamp: None,
+            },
+            Transaction::Dispute {
+                id,
+                client,
+                amount: None,
+                timestamp: None,
+            },
+        ];
+
+        // Act
+        let results = txs.map(|tx| ledger.process_tx(tx));
+
+        // Assert
+        assert!(results.iter().all(Result::is_ok));
+        let account = ledger.account(client).unwrap().unwrap();
+        assert_eq!(account.balance(None).held(), amount);
+        assert_eq!(ledger.history(client).count(), 2);
+        assert_eq!(ledger.history(other).count(), 0);
+        assert!(ledger.storage().contains_tx_id(id).unwrap());
+    }
+    #[test]
+    fn apply_batch() {
+        // Setup
+        let mut ledger = Ledger::with_storage(SledStorage::temporary().unwrap());
+        let batch = [(1, 1), (2, 2), (3, 1)].map(|(id, client)| Transaction::Deposit {
+            id: Id::from(id),
+            client: Id::from(client),
+            amount: Decimal::ONE,
+            currency: None,
+            timestamp: None,
+        });
+
+        // Act
+        let result = ledger.apply_batch(batch);
+
+        // Assert
+        assert!(result.is_ok());
+        let account = ledger.account(1.into()).unwrap().unwrap();
+        assert_eq!(account.balance(None).available(), Decimal::new(2, 0));
+        assert_eq!(ledger.get_accounts().count(), 2);
+        assert_eq!(ledger.history(1.into()).count(), 2);
+        assert!(ledger.storage().contains_tx_id(3.into()).unwrap());
+        assert!(ledger.transaction(2.into()).unwrap().is_some());
+    }
+}
@@ -0,0 +1,119 @@
+use super::{Storage, StorageIter};
+use crate::{error::Error, Account, ClientId, HistoryEntry, TxId, TxRecord};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// In-memory storage, all data is lost when it is dropped
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    pub(crate) accounts: HashMap<ClientId, Account>,
+    pub(crate) transactions: HashMap<TxId, TxRecord>,
+    pub(crate) tx_ids: HashSet<TxId>,
+    pub(crate) history: HashMap<ClientId, BTreeMap<u64, HistoryEntry>>,
+}
+
+impl MemoryStorage {
+    /// Splits the accounts and their transactions over `n` storages, the used transaction IDs
+    /// are not kept
+    pub(crate) fn split<F: Fn(ClientId) -> usize>(self, n: usize, shard: F) -> Vec<Self> {
+        let mut shards = (0..n).map(|_| Self::default()).collect::<Vec<_>>();
+        for (client, account) in self.accounts {
+            shards[shard(client)].accounts.insert(client, account);
+        }
+        for (id, record) in self.transactions {
+            let client = record.transaction.client();
+            shards[shard(client)].transactions.insert(id, record);
+        }
+        for (client, history) in self.history {
+            shards[shard(client)].history.insert(client, history);
+        }
+        shards
+    }
+
+    /// Moves everything from `other` into this storage
+    pub(crate) fn merge(&mut self, other: Self) {
+        self.accounts.extend(other.accounts);
+        self.transactions.extend(other.transactions);
+        self.tx_ids.extend(other.tx_ids);
+        for (client, history) in other.history {
+            self.history.entry(client).or_default().extend(history);
+        }
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn account(&self, client: ClientId) -> Result<Option<Account>, Error> {
+        Ok(self.accounts.get(&client).cloned())
+    }
+
+    fn put_account(&mut self, account: Account) -> Result<(), Error> {
+        self.accounts.insert(account.id, account);
+        Ok(())
+    }
+
+    fn accounts(&self) -> StorageIter<'_, Account> {
+        Box::new(self.accounts.values().cloned().map(Ok))
+    }
+
+    fn transaction(&self, id: TxId) -> Result<Option<TxRecord>, Error> {
+        Ok(self.transactions.get(&id).cloned())
+    }
+
+    fn put_transaction(&mut self, id: TxId, record: TxRecord) -> Result<(), Error> {
+        self.transactions.insert(id, record);
+        Ok(())
+    }
+
+    fn transactions(&self) -> StorageIter<'_, (TxId, TxRecord)> {
+        Box::new(
+            self.transactions
+                .iter()
+                .map(|(id, record)| Ok((*id, record.clone()))),
+        )
+    }
+
+    fn contains_tx_id(&self, id: TxId) -> Result<bool, Error> {
+        Ok(self.tx_ids.contains(&id))
+    }
+
+    fn insert_tx_id(&mut self, id: TxId) -> Result<bool, Error> {
+        Ok(self.tx_ids.insert(id))
+    }
+
+    fn tx_ids(&self) -> StorageIter<'_, TxId> {
+        Box::new(self.tx_ids.iter().copied().map(Ok))
+    }
+
+    fn history(&self, client: ClientId) -> StorageIter<'_, HistoryEntry> {
+        Box::new(
+            self.history
+                .get(&client)
+                .into_iter()
+                .flat_map(BTreeMap::values)
+                .cloned()
+                .map(Ok),
+        )
+    }
+
+    fn put_history(&mut self, entry: HistoryEntry) -> Result<(), Error> {
+        self.history
+            .entry(entry.client())
+            .or_default()
+            .insert(entry.sequence, entry);
+        Ok(())
+    }
+
+    fn histories(&self) -> StorageIter<'_, HistoryEntry> {
+        Box::new(
+            self.history
+                .values()
+                .flat_map(BTreeMap::values)
+                .cloned()
+                .map(Ok),
+        )
+    }
+
+    fn commit(&mut self, changes: MemoryStorage) -> Result<(), Error> {
+        self.merge(changes);
+        Ok(())
+    }
+}
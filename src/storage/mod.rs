@@ -0,0 +1,64 @@
+//! Storage backends for accounts and transaction history
+//!
+//! [`MemoryStorage`] keeps everything in `HashMap`s and is the default backend of a
+//! [`Ledger`](crate::Ledger). With the `sled` feature enabled, [`SledStorage`] keeps everything
+//! in an embedded key-value store on disk. An [`Overlay`] keeps changes on top of another
+//! storage in memory, to apply or discard them later.
+#[cfg(feature = "sled")]
+mod disk;
+mod memory;
+mod overlay;
+
+#[cfg(feature = "sled")]
+pub use disk::SledStorage;
+pub use memory::MemoryStorage;
+pub use overlay::Overlay;
+
+use crate::{error::Error, Account, ClientId, HistoryEntry, TxId, TxRecord};
+
+pub type StorageIter<'a, T> = Box<dyn Iterator<Item = Result<T, Error>> + 'a>;
+
+/// Storage of accounts, processed transactions, used transaction IDs and account histories
+pub trait Storage {
+    /// Gets the account with the given ID, if it exists
+    fn account(&self, client: ClientId) -> Result<Option<Account>, Error>;
+
+    /// Inserts or replaces an account
+    fn put_account(&mut self, account: Account) -> Result<(), Error>;
+
+    /// Iterates over all accounts, in no particular order
+    fn accounts(&self) -> StorageIter<'_, Account>;
+
+    /// Gets the processed transaction with the given ID, if it exists
+    fn transaction(&self, id: TxId) -> Result<Option<TxRecord>, Error>;
+
+    /// Inserts or replaces a processed transaction
+    fn put_transaction(&mut self, id: TxId, record: TxRecord) -> Result<(), Error>;
+
+    /// Iterates over all processed transactions, in no particular order
+    fn transactions(&self) -> StorageIter<'_, (TxId, TxRecord)>;
+
+    /// Checks if a transaction ID has been used
+    fn contains_tx_id(&self, id: TxId) -> Result<bool, Error>;
+
+    /// Marks a transaction ID as used, returns `false` if it was already used
+    fn insert_tx_id(&mut self, id: TxId) -> Result<bool, Error>;
+
+    /// Iterates over all used transaction IDs, in no particular order
+    fn tx_ids(&self) -> StorageIter<'_, TxId>;
+
+    /// Iterates over the history of an account, ordered on sequence number
+    fn history(&self, client: ClientId) -> StorageIter<'_, HistoryEntry>;
+
+    /// Inserts or replaces an entry in the history of the client of its transaction
+    fn put_history(&mut self, entry: HistoryEntry) -> Result<(), Error>;
+
+    /// Iterates over the history entries of all accounts, in no particular order
+    fn histories(&self) -> StorageIter<'_, HistoryEntry>;
+
+    /// Writes everything in another storage, either all of it is written or nothing is
+    ///
+    /// # Errors
+    /// Returns [`Error::Storage`] if the storage backend fails, nothing is written then
+    fn commit(&mut self, changes: MemoryStorage) -> Result<(), Error>;
+}
@@ -0,0 +1,126 @@
+use super::{MemoryStorage, Storage, StorageIter};
+use crate::{error::Error, Account, ClientId, HistoryEntry, TxId, TxRecord};
+
+/// Copy-on-write storage on top of another storage
+///
+/// Reads fall through to the base storage, writes are kept in memory and never reach the base
+/// storage. The changes can be committed to the base storage with [`Storage::commit`] once the
+/// overlay is dropped.
+pub struct Overlay<'a, S: Storage + ?Sized> {
+    base: &'a S,
+    changes: MemoryStorage,
+}
+
+impl<'a, S: Storage + ?Sized> Overlay<'a, S> {
+    pub fn new(base: &'a S) -> Self {
+        Self {
+            base,
+            changes: MemoryStorage::default(),
+        }
+    }
+
+    /// Gets the base storage
+    pub fn base(&self) -> &'a S {
+        self.base
+    }
+
+    /// Gets everything written to the overlay
+    pub fn changes(&self) -> &MemoryStorage {
+        &self.changes
+    }
+
+    /// Consumes the overlay, returning everything written to it
+    pub fn into_changes(self) -> MemoryStorage {
+        self.changes
+    }
+}
+
+impl<'a, S: Storage + ?Sized> Storage for Overlay<'a, S> {
+    fn account(&self, client: ClientId) -> Result<Option<Account>, Error> {
+        match self.changes.accounts.get(&client) {
+            Some(account) => Ok(Some(account.clone())),
+            None => self.base.account(client),
+        }
+    }
+
+    fn put_account(&mut self, account: Account) -> Result<(), Error> {
+        self.changes.put_account(account)
+    }
+
+    fn accounts(&self) -> StorageIter<'_, Account> {
+        let base = self.base.accounts().filter(|account| match account {
+            Ok(account) => !self.changes.accounts.contains_key(&account.id),
+            Err(..) => true,
+        });
+        Box::new(self.changes.accounts().chain(base))
+    }
+
+    fn transaction(&self, id: TxId) -> Result<Option<TxRecord>, Error> {
+        match self.changes.transactions.get(&id) {
+            Some(record) => Ok(Some(record.clone())),
+            None => self.base.transaction(id),
+        }
+    }
+
+    fn put_transaction(&mut self, id: TxId, record: TxRecord) -> Result<(), Error> {
+        self.changes.put_transaction(id, record)
+    }
+
+    fn transactions(&self) -> StorageIter<'_, (TxId, TxRecord)> {
+        let base = self.base.transactions().filter(|entry| match entry {
+            Ok((id, _)) => !self.changes.transactions.contains_key(id),
+            Err(..) => true,
+        });
+        Box::new(self.changes.transactions().chain(base))
+    }
+
+    fn contains_tx_id(&self, id: TxId) -> Result<bool, Error> {
+        Ok(self.changes.tx_ids.contains(&id) || self.base.contains_tx_id(id)?)
+    }
+
+    fn insert_tx_id(&mut self, id: TxId) -> Result<bool, Error> {
+        if self.base.contains_tx_id(id)? {
+            return Ok(false);
+        }
+        self.changes.insert_tx_id(id)
+    }
+
+    fn tx_ids(&self) -> StorageIter<'_, TxId> {
+        let base = self.base.tx_ids().filter(|id| match id {
+            Ok(id) => !self.changes.tx_ids.contains(id),
+            Err(..) => true,
+        });
+        Box::new(self.changes.tx_ids().chain(base))
+    }
+
+    fn history(&self, client: ClientId) -> StorageIter<'_, HistoryEntry> {
+        // Entries are appended, so the entries written to the overlay follow those of the base
+        let changes = self.changes.history.get(&client);
+        let base = self.base.history(client).filter(move |entry| match entry {
+            Ok(entry) => !changes.is_some_and(|changes| changes.contains_key(&entry.sequence)),
+            Err(..) => true,
+        });
+        Box::new(base.chain(self.changes.history(client)))
+    }
+
+    fn put_history(&mut self, entry: HistoryEntry) -> Result<(), Error> {
+        self.changes.put_history(entry)
+    }
+
+    fn histories(&self) -> StorageIter<'_, HistoryEntry> {
+        let base = self.base.histories().filter(|entry| match entry {
+            Ok(entry) => !self
+                .changes
+                .history
+                .get(&entry.client())
+                .is_some_and(|changes| changes.contains_key(&entry.sequence)),
+            Err(..) => true,
+        });
+        Box::new(self.changes.histories().chain(base))
+    }
+
+    fn commit(&mut self, changes: MemoryStorage) -> Result<(), Error> {
+        self.changes.merge(changes);
+        Ok(())
+    }
+}
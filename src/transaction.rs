@@ -1,37 +1,185 @@
-use crate::{ClientId, TxId};
+use crate::{ClientId, Currency, Timestamp, TxId};
 use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Transaction {
     Deposit {
         id: TxId,
         client: ClientId,
         amount: Decimal,
+        currency: Option<Currency>,
+        timestamp: Option<Timestamp>,
     },
     Withdrawal {
         id: TxId,
         client: ClientId,
         amount: Decimal,
+        currency: Option<Currency>,
+        timestamp: Option<Timestamp>,
     },
+    /// Moves funds from the account of `from` to the account of `to`, both or neither are
+    /// changed
+    ///
+    /// A transfer is disputed, resolved and charged back by `from`, as a single unit on both
+    /// accounts.
+    Transfer {
+        id: TxId,
+        from: ClientId,
+        to: ClientId,
+        amount: Decimal,
+        currency: Option<Currency>,
+        timestamp: Option<Timestamp>,
+    },
+    /// Holds funds of the account under the ID, until they are captured or voided
+    Authorize {
+        id: TxId,
+        client: ClientId,
+        amount: Decimal,
+        currency: Option<Currency>,
+        timestamp: Option<Timestamp>,
+    },
+    /// Withdraws the remaining held funds of the authorization with the ID, or part of them
+    /// when an amount is given
+    Capture {
+        id: TxId,
+        client: ClientId,
+        amount: Option<Decimal>,
+        timestamp: Option<Timestamp>,
+    },
+    /// Releases the remaining held funds of the authorization with the ID
+    Void {
+        id: TxId,
+        client: ClientId,
+        timestamp: Option<Timestamp>,
+    },
+    /// Holds the full amount of a transaction, or part of it when an amount is given
     Dispute {
         id: TxId,
         client: ClientId,
+        amount: Option<Decimal>,
+        timestamp: Option<Timestamp>,
     },
     Resolve {
         id: TxId,
         client: ClientId,
+        timestamp: Option<Timestamp>,
     },
     Chargeback {
         id: TxId,
         client: ClientId,
+        timestamp: Option<Timestamp>,
     },
+    /// Administrative: reactivate a locked or frozen account
+    Unlock {
+        id: TxId,
+        client: ClientId,
+        timestamp: Option<Timestamp>,
+    },
+    /// Administrative: block withdrawals, deposits are still allowed
+    Freeze {
+        id: TxId,
+        client: ClientId,
+        timestamp: Option<Timestamp>,
+    },
+    /// Administrative: permanently close an account with a zero balance
+    Close {
+        id: TxId,
+        client: ClientId,
+        timestamp: Option<Timestamp>,
+    },
+    /// Administrative: allow the available funds in a currency to go negative down to
+    /// `-amount`
+    CreditLimit {
+        id: TxId,
+        client: ClientId,
+        amount: Decimal,
+        currency: Option<Currency>,
+        timestamp: Option<Timestamp>,
+    },
+}
+
+/// Kind of a [`Transaction`], without its fields
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum TransactionKind {
+    Deposit,
+    Withdrawal,
+    Transfer,
+    Authorize,
+    Capture,
+    Void,
+    Dispute,
+    Resolve,
+    Chargeback,
+    Unlock,
+    Freeze,
+    Close,
+    CreditLimit,
 }
 
-#[derive(Debug)]
-pub(crate) enum TransactionState {
+/// Dispute state of a deposit, withdrawal or transfer, or state of an authorization
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactionState {
+    /// Not disputed, or the dispute has been resolved
     Normal,
-    Disputed,
+    /// Disputed, holding the summed amount of all disputes
+    Disputed(Decimal),
+    /// Charged back, cannot be disputed again
     Chargedback,
+    /// Open authorization, holding the amount that was not captured yet
+    Authorized(Decimal),
+    /// Authorization of which the full amount was captured
+    Captured,
+    /// Authorization that was voided or expired, the amount that was not captured was released
+    Voided,
+}
+
+/// A processed deposit, withdrawal, transfer or authorization and its state, as kept in
+/// [`Storage`](crate::storage::Storage)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TxRecord {
+    pub(crate) transaction: Transaction,
+    /// Amount the transaction applied, after rounding with the configuration at that time
+    pub(crate) amount: Decimal,
+    pub(crate) state: TransactionState,
+    /// When the transaction was processed
+    #[serde(default)]
+    pub(crate) processed: Moment,
+    /// When the open dispute of the transaction was filed, if it is disputed
+    #[serde(default)]
+    pub(crate) disputed: Option<Moment>,
+    /// Whether a dispute of the transaction has been resolved
+    #[serde(default)]
+    pub(crate) resolved: bool,
+}
+
+/// Point in the history of an account, for dispute windows and deadlines
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Moment {
+    /// Sequence number of the transaction in the history of the account
+    pub(crate) sequence: u64,
+    pub(crate) timestamp: Option<Timestamp>,
+}
+
+impl TxRecord {
+    pub(crate) fn new(transaction: Transaction, amount: Decimal, processed: Moment) -> Self {
+        Self {
+            transaction,
+            amount,
+            state: TransactionState::Normal,
+            processed,
+            disputed: None,
+            resolved: false,
+        }
+    }
+
+    pub fn transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    pub fn state(&self) -> TransactionState {
+        self.state
+    }
 }
 
 impl Transaction {
@@ -39,26 +187,102 @@ impl Transaction {
         match *self {
             Self::Deposit { id, .. }
             | Self::Withdrawal { id, .. }
+            | Self::Transfer { id, .. }
+            | Self::Authorize { id, .. }
+            | Self::Capture { id, .. }
+            | Self::Void { id, .. }
             | Self::Dispute { id, .. }
             | Self::Resolve { id, .. }
-            | Self::Chargeback { id, .. } => id,
+            | Self::Chargeback { id, .. }
+            | Self::Unlock { id, .. }
+            | Self::Freeze { id, .. }
+            | Self::Close { id, .. }
+            | Self::CreditLimit { id, .. } => id,
         }
     }
 
+    /// Gets the client that issued the transaction, `from` for a transfer
     pub fn client(&self) -> ClientId {
         match *self {
+            Self::Transfer { from, .. } => from,
             Self::Deposit { client, .. }
             | Self::Withdrawal { client, .. }
+            | Self::Authorize { client, .. }
+            | Self::Capture { client, .. }
+            | Self::Void { client, .. }
             | Self::Dispute { client, .. }
             | Self::Resolve { client, .. }
-            | Self::Chargeback { client, .. } => client,
+            | Self::Chargeback { client, .. }
+            | Self::Unlock { client, .. }
+            | Self::Freeze { client, .. }
+            | Self::Close { client, .. }
+            | Self::CreditLimit { client, .. } => client,
+        }
+    }
+
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        match *self {
+            Self::Deposit { timestamp, .. }
+            | Self::Withdrawal { timestamp, .. }
+            | Self::Transfer { timestamp, .. }
+            | Self::Authorize { timestamp, .. }
+            | Self::Capture { timestamp, .. }
+            | Self::Void { timestamp, .. }
+            | Self::Dispute { timestamp, .. }
+            | Self::Resolve { timestamp, .. }
+            | Self::Chargeback { timestamp, .. }
+            | Self::Unlock { timestamp, .. }
+            | Self::Freeze { timestamp, .. }
+            | Self::Close { timestamp, .. }
+            | Self::CreditLimit { timestamp, .. } => timestamp,
+        }
+    }
+
+    pub fn kind(&self) -> TransactionKind {
+        match self {
+            Self::Deposit { .. } => TransactionKind::Deposit,
+            Self::Withdrawal { .. } => TransactionKind::Withdrawal,
+            Self::Transfer { .. } => TransactionKind::Transfer,
+            Self::Authorize { .. } => TransactionKind::Authorize,
+            Self::Capture { .. } => TransactionKind::Capture,
+            Self::Void { .. } => TransactionKind::Void,
+            Self::Dispute { .. } => TransactionKind::Dispute,
+            Self::Resolve { .. } => TransactionKind::Resolve,
+            Self::Chargeback { .. } => TransactionKind::Chargeback,
+            Self::Unlock { .. } => TransactionKind::Unlock,
+            Self::Freeze { .. } => TransactionKind::Freeze,
+            Self::Close { .. } => TransactionKind::Close,
+            Self::CreditLimit { .. } => TransactionKind::CreditLimit,
         }
     }
 
     pub fn amount(&self) -> Option<Decimal> {
         match *self {
-            Self::Deposit { amount, .. } | Self::Withdrawal { amount, .. } => Some(amount),
+            Self::Deposit { amount, .. }
+            | Self::Withdrawal { amount, .. }
+            | Self::Transfer { amount, .. }
+            | Self::Authorize { amount, .. } => Some(amount),
             _ => None,
         }
     }
+
+    /// Gets the receiving client of a transfer
+    pub(crate) fn counterpart(&self) -> Option<ClientId> {
+        match *self {
+            Self::Transfer { to, .. } => Some(to),
+            _ => None,
+        }
+    }
+
+    /// Whether the transaction uses up its ID, which is the case for deposits, withdrawals,
+    /// transfers and authorizations
+    pub(crate) fn reserves_id(&self) -> bool {
+        matches!(
+            self,
+            Self::Deposit { .. }
+                | Self::Withdrawal { .. }
+                | Self::Transfer { .. }
+                | Self::Authorize { .. }
+        )
+    }
 }
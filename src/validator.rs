@@ -0,0 +1,73 @@
+use crate::{Account, Transaction};
+
+/// Error of a [`TransactionValidator`], reported as
+/// [`Error::Rejected`](crate::error::Error::Rejected)
+pub type ValidatorError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Business rule checked before an account processes a transaction
+///
+/// Validators are registered on a ledger with
+/// [`Ledger::add_validator`](crate::Ledger::add_validator) and run in the order they were
+/// registered, after the built-in checks of the ledger. The first validator that fails rejects
+/// the transaction. Closures with the same signature as [`TransactionValidator::validate`] are
+/// validators too.
+///
+/// # Example
+/// ```rust
+/// use ledger_rs::{
+///     error::Error, Account, Ledger, Transaction, TransactionKind, TransactionValidator,
+///     ValidatorError,
+/// };
+/// use rust_decimal::Decimal;
+///
+/// struct MaxWithdrawal(Decimal);
+///
+/// impl TransactionValidator for MaxWithdrawal {
+///     fn validate(&self, tx: &Transaction, _: &Account) -> Result<(), ValidatorError> {
+///         match tx {
+///             Transaction::Withdrawal { amount, .. } if *amount > self.0 => {
+///                 Err(format!("withdrawal of {amount} exceeds {}", self.0).into())
+///             }
+///             _ => Ok(()),
+///         }
+///     }
+/// }
+///
+/// let mut ledger = Ledger::new();
+/// ledger.add_validator(MaxWithdrawal(Decimal::new(100, 0)));
+/// ledger.add_validator(|tx: &Transaction, _: &Account| match tx.client() == 13 {
+///     true => Err("client is blocked".into()),
+///     false => Ok(()),
+/// });
+/// ledger.add_validator(|tx: &Transaction, account: &Account| {
+///     match (tx.kind(), account.count(TransactionKind::Deposit)) {
+///         (TransactionKind::Deposit, count) if count >= 100 => Err("too many deposits".into()),
+///         _ => Ok(()),
+///     }
+/// });
+/// let tx = Transaction::Deposit {
+///     id: 1.into(),
+///     client: 13.into(),
+///     amount: Decimal::new(2, 0),
+///     currency: None,
+///     timestamp: None,
+/// };
+///
+/// assert!(matches!(ledger.process_tx(tx), Err(Error::Rejected(id, ..)) if id == 1));
+/// ```
+pub trait TransactionValidator: Send + Sync {
+    /// Checks a transaction against the account of its client, before it is processed
+    ///
+    /// # Errors
+    /// Returns an error to reject the transaction
+    fn validate(&self, tx: &Transaction, account: &Account) -> Result<(), ValidatorError>;
+}
+
+impl<F> TransactionValidator for F
+where
+    F: Fn(&Transaction, &Account) -> Result<(), ValidatorError> + Send + Sync,
+{
+    fn validate(&self, tx: &Transaction, account: &Account) -> Result<(), ValidatorError> {
+        self(tx, account)
+    }
+}
@@ -128,4 +128,352 @@ mod intergration {
         assert_eq!(output, expected);
         Ok(())
     }
+
+    #[test]
+    fn dispute_withdrawal() -> Result<()> {
+        let mut cmd = Command::cargo_bin("csv_ledger")?;
+        cmd.arg("./tests/data/dispute_withdrawal.csv");
+        cmd.arg("--sort");
+        let cmd = cmd.unwrap();
+        let output = String::from_utf8(cmd.stdout)?;
+        let expected = fs::read_to_string("./tests/data/dispute_withdrawal.out")?;
+        let output = output
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+        let expected = expected
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+
+        assert_eq!(output, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn lifecycle() -> Result<()> {
+        let mut cmd = Command::cargo_bin("csv_ledger")?;
+        cmd.arg("./tests/data/lifecycle.csv");
+        cmd.arg("--sort");
+        let cmd = cmd.unwrap();
+        let output = String::from_utf8(cmd.stdout)?;
+        let expected = fs::read_to_string("./tests/data/lifecycle.out")?;
+        let output = output
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+        let expected = expected
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+
+        assert_eq!(output, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn partial_dispute() -> Result<()> {
+        let mut cmd = Command::cargo_bin("csv_ledger")?;
+        cmd.arg("./tests/data/partial_dispute.csv");
+        cmd.arg("--sort");
+        let cmd = cmd.unwrap();
+        let output = String::from_utf8(cmd.stdout)?;
+        let expected = fs::read_to_string("./tests/data/partial_dispute.out")?;
+        let output = output
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+        let expected = expected
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+
+        assert_eq!(output, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn multi_currency() -> Result<()> {
+        let mut cmd = Command::cargo_bin("csv_ledger")?;
+        cmd.arg("./tests/data/multi_currency.csv");
+        cmd.arg("--sort");
+        let cmd = cmd.unwrap();
+        let output = String::from_utf8(cmd.stdout)?;
+        let expected = fs::read_to_string("./tests/data/multi_currency.out")?;
+        let output = output
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+        let expected = expected
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+
+        assert_eq!(output, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn state() -> Result<()> {
+        let state = std::env::temp_dir().join("ledger_rs_state.json");
+        let mut cmd = Command::cargo_bin("csv_ledger")?;
+        cmd.arg("./tests/data/state_1.csv");
+        cmd.arg("--state-out");
+        cmd.arg(&state);
+        cmd.unwrap();
+
+        let mut cmd = Command::cargo_bin("csv_ledger")?;
+        cmd.arg("./tests/data/state_2.csv");
+        cmd.arg("--sort");
+        cmd.arg("--state-in");
+        cmd.arg(&state);
+        let cmd = cmd.unwrap();
+        let output = String::from_utf8(cmd.stdout)?;
+        let expected = fs::read_to_string("./tests/data/state.out")?;
+        let output = output
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+        let expected = expected
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+
+        assert_eq!(output, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn threads() -> Result<()> {
+        for name in [
+            "simple",
+            "locked",
+            "duplicate_tx",
+            "dispute_withdrawal",
+            "lifecycle",
+        ] {
+            let mut cmd = Command::cargo_bin("csv_ledger")?;
+            cmd.arg(format!("./tests/data/{name}.csv"));
+            cmd.arg("--sort");
+            cmd.arg("--threads");
+            cmd.arg("3");
+            let cmd = cmd.unwrap();
+            let output = String::from_utf8(cmd.stdout)?;
+            let expected = fs::read_to_string(format!("./tests/data/{name}.out"))?;
+            let output = output
+                .chars()
+                .filter(|c| !c.is_whitespace() || c == &'\n')
+                .collect::<String>();
+            let expected = expected
+                .chars()
+                .filter(|c| !c.is_whitespace() || c == &'\n')
+                .collect::<String>();
+
+            assert_eq!(output, expected, "{name}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn dry_run() -> Result<()> {
+        let state = std::env::temp_dir().join("ledger_rs_dry_run.json");
+        let mut cmd = Command::cargo_bin("csv_ledger")?;
+        cmd.arg("./tests/data/state_1.csv");
+        cmd.arg("--state-out");
+        cmd.arg(&state);
+        cmd.unwrap();
+
+        let mut cmd = Command::cargo_bin("csv_ledger")?;
+        cmd.arg("./tests/data/state_2.csv");
+        cmd.arg("--dry-run");
+        cmd.arg("--state-in");
+        cmd.arg(&state);
+        let cmd = cmd.unwrap();
+        let output = String::from_utf8(cmd.stdout)?;
+        let expected = fs::read_to_string("./tests/data/dry_run.out")?;
+        let output = output
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+        let expected = expected
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+
+        assert_eq!(output, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn timestamps() -> Result<()> {
+        let mut cmd = Command::cargo_bin("csv_ledger")?;
+        cmd.arg("./tests/data/timestamps.csv");
+        cmd.arg("--sort");
+        cmd.arg("--reject-out-of-order");
+        let cmd = cmd.unwrap();
+        let output = String::from_utf8(cmd.stdout)?;
+        let expected = fs::read_to_string("./tests/data/timestamps.out")?;
+        let output = output
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+        let expected = expected
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+
+        assert_eq!(output, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn config() -> Result<()> {
+        let mut cmd = Command::cargo_bin("csv_ledger")?;
+        cmd.arg("./tests/data/config.csv");
+        cmd.arg("--sort");
+        cmd.arg("--config");
+        cmd.arg("./tests/data/config.toml");
+        let cmd = cmd.unwrap();
+        let output = String::from_utf8(cmd.stdout)?;
+        let expected = fs::read_to_string("./tests/data/config.out")?;
+        let output = output
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+        let expected = expected
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+
+        assert_eq!(output, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn credit() -> Result<()> {
+        let mut cmd = Command::cargo_bin("csv_ledger")?;
+        cmd.arg("./tests/data/credit.csv");
+        cmd.arg("--sort");
+        cmd.arg("--clients");
+        cmd.arg("./tests/data/credit_clients.csv");
+        let cmd = cmd.unwrap();
+        let output = String::from_utf8(cmd.stdout)?;
+        let expected = fs::read_to_string("./tests/data/credit.out")?;
+        let output = output
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+        let expected = expected
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+
+        assert_eq!(output, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects() -> Result<()> {
+        let expected = fs::read_to_string("./tests/data/rejects.out")?;
+        for threads in ["1", "3"] {
+            let rejects = std::env::temp_dir().join(format!("ledger_rs_rejects_{threads}.csv"));
+            let mut cmd = Command::cargo_bin("csv_ledger")?;
+            cmd.arg("./tests/data/rejects.csv");
+            cmd.arg("--threads");
+            cmd.arg(threads);
+            cmd.arg("--rejects");
+            cmd.arg(&rejects);
+            cmd.unwrap();
+            let output = fs::read_to_string(&rejects)?;
+
+            assert_eq!(output, expected, "{threads} threads");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn transfer() -> Result<()> {
+        let mut cmd = Command::cargo_bin("csv_ledger")?;
+        cmd.arg("./tests/data/transfer.csv");
+        cmd.arg("--sort");
+        let cmd = cmd.unwrap();
+        let output = String::from_utf8(cmd.stdout)?;
+        let expected = fs::read_to_string("./tests/data/transfer.out")?;
+        let output = output
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+        let expected = expected
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+
+        assert_eq!(output, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn authorization() -> Result<()> {
+        let mut cmd = Command::cargo_bin("csv_ledger")?;
+        cmd.arg("./tests/data/authorization.csv");
+        cmd.arg("--sort");
+        let cmd = cmd.unwrap();
+        let output = String::from_utf8(cmd.stdout)?;
+        let expected = fs::read_to_string("./tests/data/authorization.out")?;
+        let output = output
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+        let expected = expected
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+
+        assert_eq!(output, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn rounding() -> Result<()> {
+        let mut cmd = Command::cargo_bin("csv_ledger")?;
+        cmd.arg("./tests/data/rounding.csv");
+        cmd.arg("--sort");
+        cmd.arg("--config");
+        cmd.arg("./tests/data/rounding.toml");
+        let cmd = cmd.unwrap();
+        let output = String::from_utf8(cmd.stdout)?;
+        let expected = fs::read_to_string("./tests/data/rounding.out")?;
+        let output = output
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+        let expected = expected
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+
+        assert_eq!(output, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn wide_ids() -> Result<()> {
+        let mut cmd = Command::cargo_bin("csv_ledger")?;
+        cmd.arg("./tests/data/wide_ids.csv");
+        cmd.arg("--sort");
+        let cmd = cmd.unwrap();
+        let output = String::from_utf8(cmd.stdout)?;
+        let expected = fs::read_to_string("./tests/data/wide_ids.out")?;
+        let output = output
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+        let expected = expected
+            .chars()
+            .filter(|c| !c.is_whitespace() || c == &'\n')
+            .collect::<String>();
+
+        assert_eq!(output, expected);
+        Ok(())
+    }
 }